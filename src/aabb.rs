@@ -1,4 +1,7 @@
-use crate::{ray::Ray, vec3::Point};
+use crate::{
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
 
 /// AABB stays for Axis-Aligned Bounding Box.
 #[derive(Clone)]
@@ -12,12 +15,18 @@ impl AABB {
         Self { min, max }
     }
 
-    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        let inv_dir = Vec3::new(r.dir.x.recip(), r.dir.y.recip(), r.dir.z.recip());
+        self.hit_inv(r.orig, inv_dir, t_min, t_max)
+    }
+
+    /// hit_inv is the slab test with the inverse of the ray direction computed by the caller,
+    /// so that it can be reused for every box tested against the same ray.
+    pub fn hit_inv(&self, orig: Point, inv_dir: Vec3, mut t_min: f64, mut t_max: f64) -> bool {
         for a in 0..3 {
-            let inv_d = r.dir[a].recip();
-            let mut t0 = (self.min[a] - r.orig[a]) * inv_d;
-            let mut t1 = (self.max[a] - r.orig[a]) * inv_d;
-            if inv_d < 0.0 {
+            let mut t0 = (self.min[a] - orig[a]) * inv_dir[a];
+            let mut t1 = (self.max[a] - orig[a]) * inv_dir[a];
+            if inv_dir[a] < 0.0 {
                 (t0, t1) = (t1, t0)
             }
             t_min = t0.max(t_min);
@@ -29,6 +38,15 @@ impl AABB {
         true
     }
 
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
     pub fn union(&self, other: &Self) -> Self {
         let small = Point::new(
             f64::min(self.min.x, other.min.x),
@@ -1,10 +1,12 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
     aabb::AABB,
     hittable::{HitRecord, Hittable},
+    light::{area_to_solid_angle, Light, LightSample},
     material::Material,
     ray::Ray,
+    sampler::Sampler,
     vec3::{Point, Vec3},
 };
 
@@ -14,11 +16,11 @@ pub struct XYRect {
     pub y0: f64,
     pub y1: f64,
     pub k: f64,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
 }
 
 impl XYRect {
-    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, mat: Arc<dyn Material>) -> Self {
         Self {
             x0,
             x1,
@@ -38,7 +40,7 @@ impl Hittable for XYRect {
         ))
     }
 
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.k - r.orig.z) / r.dir.z;
         if t < t_min || t_max < t {
             return None;
@@ -59,23 +61,54 @@ impl Hittable for XYRect {
             r,
             u,
             v,
-            self.mat.clone(),
+            self.mat.as_ref(),
             Vec3::z(1.0),
         ))
     }
 }
 
+impl Light for XYRect {
+    fn sample(&self, sampler: &mut dyn Sampler, origin: Point) -> Option<LightSample> {
+        let (u, v) = sampler.get_2d();
+        let p = Point::new(
+            self.x0 + u * (self.x1 - self.x0),
+            self.y0 + v * (self.y1 - self.y0),
+            self.k,
+        );
+        let area = (self.x1 - self.x0) * (self.y1 - self.y0);
+        let (wi, pdf) = area_to_solid_angle(area.recip(), origin, p, Vec3::z(1.0))?;
+        Some(LightSample {
+            p,
+            wi,
+            pdf,
+            emitted: self.mat.emitted(u, v, &p),
+        })
+    }
+
+    fn pdf_value(&self, origin: Point, dir: Vec3) -> f64 {
+        let Some(rec) = self.hit(&Ray::new(origin, dir, 0.0), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let area = (self.x1 - self.x0) * (self.y1 - self.y0);
+        area_to_solid_angle(area.recip(), origin, rec.p, Vec3::z(1.0)).map_or(0.0, |(_, pdf)| pdf)
+    }
+
+    fn material(&self) -> &dyn Material {
+        self.mat.as_ref()
+    }
+}
+
 pub struct XZRect {
     pub x0: f64,
     pub x1: f64,
     pub z0: f64,
     pub z1: f64,
     pub k: f64,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
 }
 
 impl XZRect {
-    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, mat: Arc<dyn Material>) -> Self {
         Self {
             x0,
             x1,
@@ -95,7 +128,7 @@ impl Hittable for XZRect {
         ))
     }
 
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.k - r.orig.y) / r.dir.y;
         if t < t_min || t_max < t {
             return None;
@@ -116,23 +149,54 @@ impl Hittable for XZRect {
             r,
             u,
             v,
-            self.mat.clone(),
+            self.mat.as_ref(),
             Vec3::y(1.0),
         ))
     }
 }
 
+impl Light for XZRect {
+    fn sample(&self, sampler: &mut dyn Sampler, origin: Point) -> Option<LightSample> {
+        let (u, v) = sampler.get_2d();
+        let p = Point::new(
+            self.x0 + u * (self.x1 - self.x0),
+            self.k,
+            self.z0 + v * (self.z1 - self.z0),
+        );
+        let area = (self.x1 - self.x0) * (self.z1 - self.z0);
+        let (wi, pdf) = area_to_solid_angle(area.recip(), origin, p, Vec3::y(1.0))?;
+        Some(LightSample {
+            p,
+            wi,
+            pdf,
+            emitted: self.mat.emitted(u, v, &p),
+        })
+    }
+
+    fn pdf_value(&self, origin: Point, dir: Vec3) -> f64 {
+        let Some(rec) = self.hit(&Ray::new(origin, dir, 0.0), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let area = (self.x1 - self.x0) * (self.z1 - self.z0);
+        area_to_solid_angle(area.recip(), origin, rec.p, Vec3::y(1.0)).map_or(0.0, |(_, pdf)| pdf)
+    }
+
+    fn material(&self) -> &dyn Material {
+        self.mat.as_ref()
+    }
+}
+
 pub struct YZRect {
     pub y0: f64,
     pub y1: f64,
     pub z0: f64,
     pub z1: f64,
     pub k: f64,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
 }
 
 impl YZRect {
-    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, mat: Arc<dyn Material>) -> Self {
         Self {
             y0,
             y1,
@@ -152,7 +216,7 @@ impl Hittable for YZRect {
         ))
     }
 
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.k - r.orig.x) / r.dir.x;
         if t < t_min || t_max < t {
             return None;
@@ -173,8 +237,39 @@ impl Hittable for YZRect {
             r,
             u,
             v,
-            self.mat.clone(),
+            self.mat.as_ref(),
             Vec3::x(1.0),
         ))
     }
 }
+
+impl Light for YZRect {
+    fn sample(&self, sampler: &mut dyn Sampler, origin: Point) -> Option<LightSample> {
+        let (u, v) = sampler.get_2d();
+        let p = Point::new(
+            self.k,
+            self.y0 + v * (self.y1 - self.y0),
+            self.z0 + u * (self.z1 - self.z0),
+        );
+        let area = (self.y1 - self.y0) * (self.z1 - self.z0);
+        let (wi, pdf) = area_to_solid_angle(area.recip(), origin, p, Vec3::x(1.0))?;
+        Some(LightSample {
+            p,
+            wi,
+            pdf,
+            emitted: self.mat.emitted(u, v, &p),
+        })
+    }
+
+    fn pdf_value(&self, origin: Point, dir: Vec3) -> f64 {
+        let Some(rec) = self.hit(&Ray::new(origin, dir, 0.0), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let area = (self.y1 - self.y0) * (self.z1 - self.z0);
+        area_to_solid_angle(area.recip(), origin, rec.p, Vec3::x(1.0)).map_or(0.0, |(_, pdf)| pdf)
+    }
+
+    fn material(&self) -> &dyn Material {
+        self.mat.as_ref()
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use crate::{color::Color, hittable::HitRecord, material::Material, ray::Ray};
+
+/// Aov is an arbitrary output variable, a property of the first surface seen through a pixel
+/// written next to the rendered image, e.g. for compositing or denoising.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    // Depth is the distance from the camera, 0 where nothing is hit.
+    Depth,
+    // Normal is the world space normal facing the camera.
+    Normal,
+    Albedo,
+    Position,
+    Uv,
+    // ObjectId is the id set by `ObjectId`, scenes number their objects from 1.
+    ObjectId,
+    // MaterialId numbers materials from 1 in the order given to `Renderer::with_materials`.
+    MaterialId,
+    // Alpha is 1 where a surface is hit and 0 where the background is seen.
+    Alpha,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 8] = [
+        Self::Depth,
+        Self::Normal,
+        Self::Albedo,
+        Self::Position,
+        Self::Uv,
+        Self::ObjectId,
+        Self::MaterialId,
+        Self::Alpha,
+    ];
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Depth => "depth",
+            Self::Normal => "normal",
+            Self::Albedo => "albedo",
+            Self::Position => "position",
+            Self::Uv => "uv",
+            Self::ObjectId => "object_id",
+            Self::MaterialId => "material_id",
+            Self::Alpha => "alpha",
+        }
+    }
+
+    /// channels names the channels of the AOV, in the order of the color components.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Self::Depth => &["Z"],
+            Self::Normal | Self::Position => &["X", "Y", "Z"],
+            Self::Albedo => &["R", "G", "B"],
+            Self::Uv => &["U", "V"],
+            Self::ObjectId | Self::MaterialId => &["id"],
+            Self::Alpha => &["A"],
+        }
+    }
+
+    /// is_id returns true for ids, which are taken from the first sample of a pixel
+    /// since averages of them are meaningless.
+    pub fn is_id(self) -> bool {
+        matches!(self, Self::ObjectId | Self::MaterialId)
+    }
+
+    /// evaluate returns the value of the AOV for the camera ray `r` hitting `rec` first.
+    pub fn evaluate(
+        self,
+        r: &Ray,
+        rec: Option<&HitRecord>,
+        materials: &[Arc<dyn Material>],
+    ) -> Color {
+        let Some(rec) = rec else {
+            return Color::default();
+        };
+        let scalar = |x: f64| Color::new(x, x, x);
+        match self {
+            Self::Depth => scalar(rec.t * r.dir.length()),
+            Self::Normal => Color::new_from_vec3(rec.normal),
+            Self::Albedo => rec.material.albedo(rec),
+            Self::Position => Color::new_from_vec3(rec.p),
+            Self::Uv => Color::new(rec.u, rec.v, 0.0),
+            Self::ObjectId => scalar(rec.object_id as f64),
+            Self::MaterialId => {
+                let id = materials
+                    .iter()
+                    .position(|m| std::ptr::addr_eq(Arc::as_ptr(m), rec.material))
+                    .map_or(0, |i| i + 1);
+                scalar(id as f64)
+            }
+            Self::Alpha => scalar(1.0),
+        }
+    }
+}
@@ -1,529 +1,513 @@
 use std::error::Error;
-use std::rc::Rc;
-
-use rayst::aarect::{XYRect, XZRect, YZRect};
-use rayst::box3d::Box3D;
-use rayst::bvh::BVHNode;
-use rayst::constant_medium::ConstantMedium;
-use rayst::hittable::{RotateY, Translate};
-use rayst::material::{Dielectric, DiffuseLight, Lambertian, Metal};
-use rayst::moving_sphere::MovingSphere;
-use rayst::texture::{CheckerTexture, ImageTexture, NoiseTexture};
-use rayst::vec3::Vec3;
-use rayst::{
-    camera::Camera, color::Color, hittable::Hittable, hittable_list::HittableList, ray::Ray,
-    sphere::Sphere, vec3::Point,
-};
-
-use rand::Rng;
-
-fn main() -> Result<(), Box<dyn Error>> {
-    // Image.
-    let mut aspect_ratio: f64 = 16.0 / 9.0;
-    let mut image_width: u32 = 400;
-    let mut samples_per_pixel: u32 = 100;
-    let max_depth = 50;
-
-    // Random number generator.
-    let mut rng = rayst::thread_rng();
-
-    // World.
-    let mut look_from = Point::new(13.0, 2.0, 3.0);
-    let mut look_at = Point::default();
-    let mut vfov = 20.0;
-    let mut background = Color::default();
-    let mut aperture = 0.0;
-    let scene = match 0 {
-        1 => {
-            background = Color::new(0.7, 0.8, 1.0);
-            aperture = 0.1;
-            random_scene()
-        }
-        2 => {
-            background = Color::new(0.7, 0.8, 1.0);
-            two_spheres()
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, ValueEnum};
+
+use rayst::aov::Aov;
+use rayst::denoise::Denoiser;
+use rayst::film::Film;
+use rayst::filter::FilterKind;
+use rayst::output::OutputFormat;
+use rayst::render::{MisPathTracer, NeePathTracer, RenderSettings, Renderer, DENOISER_AOVS};
+use rayst::sampler::SamplerKind;
+use rayst::scene::SceneDescription;
+use rayst::tonemap::ToneMapping;
+
+/// Renders a TOML scene description into an image file.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Scene description file.
+    #[arg(short, long)]
+    scene: PathBuf,
+
+    /// Output image, the format is chosen by the extension: .png, .hdr or .exr.
+    #[arg(short, long, default_value = "image.png")]
+    output: PathBuf,
+
+    /// Bits per channel of PNG output.
+    #[arg(long, default_value_t = 8, value_parser = parse_bit_depth)]
+    bit_depth: u8,
+
+    /// Image width in pixels, the height keeps the scene aspect ratio unless given.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+
+    /// Image height in pixels.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    height: Option<u32>,
+
+    /// Samples per pixel, the maximum with adaptive sampling.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    spp: Option<u32>,
+
+    /// Minimum samples per pixel of adaptive sampling.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    min_spp: Option<u32>,
+
+    /// Relative error at which adaptive sampling stops sampling a pixel, 0 disables it.
+    #[arg(long, value_parser = parse_non_negative)]
+    adaptive_threshold: Option<f64>,
+
+    /// Writes the number of samples taken per pixel as a PNG heatmap.
+    #[arg(long, value_name = "PATH")]
+    spp_heatmap: Option<PathBuf>,
+
+    /// AOVs to write, comma separated: depth, normal, albedo, position, uv, object_id,
+    /// material_id or alpha. EXR output stores them as layers, other formats as separate
+    /// images named like image.depth.png.
+    #[arg(long, value_delimiter = ',', value_parser = parse_aov)]
+    aov: Vec<Aov>,
+
+    /// Denoises the image, albedo and normal AOVs are added to guide the denoiser.
+    #[arg(long)]
+    denoise: bool,
+
+    /// Maximum number of bounces of a path.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_depth: Option<u32>,
+
+    /// Number of bounces after which paths may be ended by Russian roulette.
+    #[arg(long)]
+    rr_min_depth: Option<u32>,
+
+    /// Sampler: independent, stratified, halton, sobol or blue-noise.
+    #[arg(long, value_parser = parse_sampler)]
+    sampler: Option<SamplerKind>,
+
+    /// Pixel reconstruction filter: box, tent, gaussian, mitchell or lanczos.
+    #[arg(long, value_parser = parse_filter)]
+    filter: Option<FilterKind>,
+
+    /// Radius of the reconstruction filter in pixels.
+    #[arg(long, value_parser = parse_positive)]
+    filter_radius: Option<f64>,
+
+    /// Seed of random generators.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Number of render threads, all cores are used by default.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    threads: Option<u32>,
+
+    /// Camera position as x,y,z.
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_vec3)]
+    look_from: Option<[f64; 3]>,
+
+    /// Point the camera looks at as x,y,z.
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_vec3)]
+    look_at: Option<[f64; 3]>,
+
+    /// Vertical field of view in degrees.
+    #[arg(long, value_parser = parse_vfov)]
+    vfov: Option<f64>,
+
+    /// Lens aperture, 0 disables depth of field.
+    #[arg(long, value_parser = parse_non_negative)]
+    aperture: Option<f64>,
+
+    /// Distance to the plane in focus.
+    #[arg(long, value_parser = parse_positive)]
+    focus_dist: Option<f64>,
+
+    /// Tone mapping operator: clamp, reinhard, extended-reinhard, aces or hable. Clamping keeps
+    /// radiance up to 1 as it is, scenes with bright lights in view need one of the others.
+    #[arg(long, default_value = "clamp", value_parser = parse_tonemap)]
+    tonemap: String,
+
+    /// Exposure adjustment in stops applied before tone mapping.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true, value_parser = parse_number)]
+    exposure: f64,
+
+    /// Stops rendering after this many seconds, the image keeps the samples taken so far.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_positive)]
+    time_limit: Option<f64>,
+
+    /// Writes the image every this many seconds while rendering.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_positive)]
+    write_interval: Option<f64>,
+
+    /// Writes the image after every this many passes over the image.
+    #[arg(long, value_name = "PASSES", value_parser = clap::value_parser!(u32).range(1..))]
+    write_every: Option<u32>,
+
+    /// Saves accumulated samples to this file whenever the image is written.
+    #[arg(long, value_name = "PATH")]
+    checkpoint: Option<PathBuf>,
+
+    /// Continues the render saved in the checkpoint file.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// Light transport algorithm.
+    #[arg(long, value_enum, default_value_t = IntegratorKind::Mis)]
+    integrator: IntegratorKind,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum IntegratorKind {
+    /// Path tracing which finds lights only by scattering.
+    Path,
+    /// Path tracing which also samples lights directly at every diffuse bounce.
+    Nee,
+    /// Path tracing combining material and light sampling with multiple importance sampling.
+    Mis,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
         }
-        3 => {
-            background = Color::new(0.7, 0.8, 1.0);
-            two_perlin_spheres(&mut rng)
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    // Fail early instead of after the image is rendered.
+    let format = OutputFormat::from_path(&args.output)?.with_bit_depth(args.bit_depth);
+
+    let mut desc = SceneDescription::from_file(&args.scene)?;
+    apply_overrides(&args, &mut desc);
+    let settings = fingerprinted_settings(&args, &desc);
+
+    let mut scene = desc.build(rayst::scene::base_dir(&args.scene))?;
+    let fingerprint = fingerprint(&args, &settings, &scene.files)?;
+    if let Some(threads) = args.threads {
+        scene.settings.threads = threads as usize;
+    }
+    let cam = scene.camera();
+
+    let mut film = match &args.checkpoint {
+        Some(path) if args.resume => {
+            let film = rayst::checkpoint::load(path, fingerprint)?;
+            let samples = film.sample_counts().into_iter().max().unwrap_or(0);
+            eprintln!(
+                "Resuming {} with {} samples per pixel.",
+                path.display(),
+                samples
+            );
+            film
         }
-        4 => {
-            background = Color::new(0.7, 0.8, 1.0);
-            earth()
+        _ => {
+            let mut aovs = args.aov.clone();
+            if scene.settings.denoise {
+                aovs.extend(DENOISER_AOVS.iter().filter(|aov| !args.aov.contains(aov)));
+            }
+            Film::new(scene.settings.width, scene.settings.height).with_aovs(&aovs)
         }
-        5 => {
-            samples_per_pixel = 400;
-            look_from = Point::new(26.0, 3.0, 6.0);
-            look_at = Point::y(2.0);
-            simple_light(&mut rng)
+    }
+    .with_filter(scene.settings.filter());
+
+    eprintln!(
+        "Rendering {}x{} with {} samples per pixel.",
+        scene.settings.width, scene.settings.height, scene.settings.samples_per_pixel
+    );
+    let mut renderer = Renderer::new(&*scene.world, &cam, scene.background, scene.settings)
+        .with_materials(&scene.materials);
+    let RenderSettings {
+        max_depth,
+        rr_min_depth,
+        denoise,
+        media,
+        ..
+    } = *renderer.settings();
+    match args.integrator {
+        IntegratorKind::Path => {}
+        IntegratorKind::Nee => {
+            let integrator = NeePathTracer::new(max_depth, scene.lights)
+                .with_russian_roulette(rr_min_depth)
+                .with_media(media);
+            renderer = renderer.with_integrator(integrator);
         }
-        6 => {
-            aspect_ratio = 1.0;
-            image_width = 600;
-            samples_per_pixel = 400;
-            look_from = Point::new(278.0, 278.0, -800.0);
-            look_at = Point::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
-            cornell_box()
+        IntegratorKind::Mis => {
+            let integrator = MisPathTracer::new(max_depth, scene.lights)
+                .with_russian_roulette(rr_min_depth)
+                .with_media(media);
+            renderer = renderer.with_integrator(integrator);
         }
-        7 => {
-            aspect_ratio = 1.0;
-            image_width = 600;
-            samples_per_pixel = 200;
-            look_from = Point::new(278.0, 278.0, -800.0);
-            look_at = Point::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
-            cornell_smoke()
+    }
+
+    let tone_mapping = ToneMapping::new(
+        args.exposure,
+        rayst::tonemap::by_name(&args.tonemap).unwrap(),
+    );
+    let write = |film: &Film| -> Result<(), Box<dyn Error>> {
+        // Checkpoints keep the samples, images are denoised.
+        let denoised;
+        let image = if denoise {
+            denoised = Denoiser::new().denoise(film);
+            &denoised
+        } else {
+            film
+        };
+        rayst::output::save_with_format(image, &args.output, format, &tone_mapping)?;
+        rayst::output::save_aovs(image, &args.output, format)?;
+        if let Some(path) = &args.spp_heatmap {
+            rayst::output::save_sample_heatmap(film, path)?;
         }
-        _ => {
-            aspect_ratio = 1.0;
-            image_width = 800;
-            samples_per_pixel = 10000;
-            look_from = Point::new(478.0, 278.0, -600.0);
-            look_at = Point::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
-            final_scene(&mut rng)
+        if let Some(path) = &args.checkpoint {
+            rayst::checkpoint::save(film, fingerprint, path)?;
         }
+        Ok(())
     };
 
-    let image_height = (image_width as f64 / aspect_ratio) as u32;
-    //let world = BVHNode::from_hittable_list(scene, 0.0, 1.0);
-    let world = scene;
-
-    // Camera.
-    let vup = Vec3::y(1.0);
-    let dist_to_focus = 10.0;
-
-    let cam = Camera::new(
-        look_from,
-        look_at,
-        vup,
-        vfov,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-        0.0..1.0,
-    );
-
-    println!("P3\n{} {}\n255", image_width, image_height);
-    for j in (0..image_height).rev() {
-        eprint!("\rScanlines remaining: {}", j);
-
-        for i in 0..image_width {
-            let mut c = Color::default();
-            for _ in 0..samples_per_pixel {
-                let u = (i as f64 + rng.gen::<f64>()) / (image_width - 1) as f64;
-                let v = (j as f64 + rng.gen::<f64>()) / (image_height - 1) as f64;
-                let r = cam.get_ray(&mut rng, u, v);
-                c += ray_color(&mut rng, &r, background, &world, max_depth);
+    let deadline = args
+        .time_limit
+        .map(|limit| Instant::now() + Duration::from_secs_f64(limit));
+    let mut passes = 0u32;
+    let mut last_write = Instant::now();
+    let mut write_error = None;
+    let finished = renderer.render_into(
+        &mut film,
+        deadline,
+        |samples, done, total| {
+            eprint!(
+                "\rPass up to {:5} samples per pixel, tiles remaining: {:5}",
+                samples,
+                total - done
+            );
+        },
+        |film, _| {
+            passes += 1;
+            let due = args.write_every.is_some_and(|n| passes.is_multiple_of(n))
+                || args
+                    .write_interval
+                    .is_some_and(|interval| last_write.elapsed().as_secs_f64() >= interval);
+            if due {
+                if let Err(err) = write(film) {
+                    write_error = Some(err);
+                    return ControlFlow::Break(());
+                }
+                last_write = Instant::now();
             }
-            println!("{}", c.adjust_and_format(samples_per_pixel));
-        }
+            ControlFlow::Continue(())
+        },
+    );
+    if let Some(err) = write_error {
+        return Err(err);
     }
 
-    eprintln!("\nDone.");
+    let counts = film.sample_counts();
+    let mean = counts.iter().map(|&n| n as f64).sum::<f64>() / counts.len() as f64;
+    if !finished {
+        eprint!("\nStopped at the time limit.");
+    }
+    eprint!("\nTook {:.1} samples per pixel on average.", mean);
+    write(&film)?;
+    eprintln!("\nDone, image is written to {}.", args.output.display());
 
     Ok(())
 }
 
-fn ray_color(
-    rng: &mut rayst::Rng,
-    r: &Ray,
-    background: Color,
-    world: &impl Hittable,
-    depth: i32,
-) -> Color {
-    if depth <= 0 {
-        return Color::default();
-    }
+/// fingerprinted_settings formats the settings of the description a checkpoint depends on.
+/// Sample counts, threads and the time limit may change between runs, except for samplers
+/// whose samples depend on the number of samples per pixel.
+fn fingerprinted_settings(args: &Args, desc: &SceneDescription) -> String {
+    let render = &desc.render;
+    let samples_per_pixel = (!render.sampler.is_progressive()).then_some(render.samples_per_pixel);
+    format!(
+        "{:?}",
+        (
+            (render.width, render.height, render.aspect_ratio),
+            (
+                render.seed,
+                render.sampler,
+                samples_per_pixel,
+                render.max_depth,
+                render.rr_min_depth
+            ),
+            (render.filter, render.filter_radius),
+            // Denoised renders need the guide AOVs added to fresh films.
+            render.denoise,
+            &desc.camera,
+            args.integrator,
+            &args.aov,
+        )
+    )
+}
 
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        if let Some((scattered, attenuation)) = rec.material.scatter(rng, r, &rec) {
-            return attenuation * ray_color(rng, &scattered, background, world, depth - 1);
-        }
-        return rec.material.emitted(rec.u, rec.v, &rec.p);
+/// fingerprint identifies the image a render produces, so that only renders of the same image
+/// resume a checkpoint. It covers the scene file, the files it references and `settings`.
+fn fingerprint(args: &Args, settings: &str, files: &[PathBuf]) -> Result<u64, Box<dyn Error>> {
+    let mut contents = vec![fs::read(&args.scene)?, settings.as_bytes().to_vec()];
+    for file in files {
+        contents.push(fs::read(file)?);
     }
-
-    background
+    let parts: Vec<&[u8]> = contents.iter().map(Vec::as_slice).collect();
+    Ok(rayst::checkpoint::fingerprint(&parts))
 }
 
-fn random_scene() -> HittableList {
-    let mut world = HittableList::default();
-
-    let checker = Rc::new(CheckerTexture::new(
-        Color::new(0.2, 0.3, 0.1),
-        Color::new(0.9, 0.9, 0.9),
-    ));
-    world.add(Rc::new(Sphere::new(
-        Point::y(-1000.0),
-        1000.0,
-        Rc::new(Lambertian::new_with_texture(checker)),
-    )));
-
-    let mut rng = rand::thread_rng();
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat: f64 = (&mut rng).gen();
-            let center = Point::new(
-                a as f64 + 0.9 * rng.gen::<f64>(),
-                0.2,
-                b as f64 + 0.9 * rng.gen::<f64>(),
-            );
-
-            if (center - Point::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let object: Rc<dyn Hittable> = if choose_mat < 0.8 {
-                    let albedo: Color = rng.gen::<Color>(); //+ rng.gen::<Color>();
-                    let center2 = center + Vec3::y(rng.gen_range(0.0..0.5));
-                    Rc::new(MovingSphere::new(
-                        center,
-                        center2,
-                        0.0..1.0,
-                        0.2,
-                        Rc::new(Lambertian::new(albedo)),
-                    ))
-                } else if choose_mat < 0.95 {
-                    let albedo: Color = Color::new(
-                        rng.gen_range(0.5..1.0),
-                        rng.gen_range(0.5..1.0),
-                        rng.gen_range(0.5..1.0),
-                    );
-                    Rc::new(Sphere::new(
-                        center,
-                        0.2,
-                        Rc::new(Metal::new(albedo, rng.gen_range(0.0..0.5))),
-                    ))
-                } else {
-                    Rc::new(Sphere::new(center, 0.2, Rc::new(Dielectric::new(1.5))))
-                };
-
-                world.add(object);
+fn apply_overrides(args: &Args, desc: &mut SceneDescription) {
+    let render = &mut desc.render;
+    match (args.width, args.height) {
+        (Some(width), Some(height)) => {
+            render.width = width;
+            render.height = Some(height);
+        }
+        (Some(width), None) => {
+            // Keep the aspect ratio of the scene.
+            if let Some(height) = render.height {
+                render.aspect_ratio = render.width as f64 / height as f64;
             }
+            render.width = width;
+            render.height = None;
         }
+        (None, Some(height)) => {
+            let aspect_ratio = render
+                .height
+                .map_or(render.aspect_ratio, |h| render.width as f64 / h as f64);
+            render.width = ((height as f64 * aspect_ratio) as u32).max(1);
+            render.height = Some(height);
+        }
+        (None, None) => {}
+    }
+    if let Some(spp) = args.spp {
+        render.samples_per_pixel = spp;
+    }
+    if let Some(min_spp) = args.min_spp {
+        render.min_samples_per_pixel = min_spp;
+    }
+    if let Some(threshold) = args.adaptive_threshold {
+        render.adaptive_threshold = threshold;
+    }
+    if let Some(max_depth) = args.max_depth {
+        render.max_depth = max_depth;
+    }
+    if let Some(rr_min_depth) = args.rr_min_depth {
+        render.rr_min_depth = rr_min_depth;
+    }
+    if let Some(seed) = args.seed {
+        render.seed = seed;
+    }
+    if let Some(sampler) = args.sampler {
+        render.sampler = sampler;
+    }
+    if let Some(filter) = args.filter {
+        render.filter = filter;
+        // The radius of the scene belongs to its filter.
+        render.filter_radius = None;
+    }
+    if let Some(radius) = args.filter_radius {
+        render.filter_radius = Some(radius);
+    }
+    if args.denoise {
+        render.denoise = true;
     }
 
-    let material1 = Rc::new(Dielectric::new(1.5));
-    world.add(Rc::new(Sphere::new(Point::y(1.0), 1.0, material1)));
-
-    let material2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Rc::new(Sphere::new(
-        Point::new(-4.0, 1.0, 0.0),
-        1.0,
-        material2,
-    )));
-
-    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Rc::new(Sphere::new(
-        Point::new(4.0, 1.0, 0.0),
-        1.0,
-        material3,
-    )));
-
-    world
+    let camera = &mut desc.camera;
+    if let Some(look_from) = args.look_from {
+        camera.look_from = look_from;
+    }
+    if let Some(look_at) = args.look_at {
+        camera.look_at = look_at;
+    }
+    if let Some(vfov) = args.vfov {
+        camera.vfov = vfov;
+    }
+    if let Some(aperture) = args.aperture {
+        camera.aperture = aperture;
+    }
+    if let Some(focus_dist) = args.focus_dist {
+        camera.focus_dist = focus_dist;
+    }
 }
 
-fn two_spheres() -> HittableList {
-    let mut world = HittableList::default();
-    let checker = Rc::new(CheckerTexture::new(
-        Color::new(0.2, 0.3, 0.1),
-        Color::new(0.9, 0.9, 0.9),
-    ));
-
-    world.add(Rc::new(Sphere::new(
-        Point::y(-10.0),
-        10.0,
-        Rc::new(Lambertian::new_with_texture(checker.clone())),
-    )));
-    world.add(Rc::new(Sphere::new(
-        Point::y(10.0),
-        10.0,
-        Rc::new(Lambertian::new_with_texture(checker)),
-    )));
-    world
+fn parse_vec3(s: &str) -> Result<[f64; 3], String> {
+    let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+    let [x, y, z] = parts[..] else {
+        return Err(format!(
+            "expected three comma separated numbers, got '{}'",
+            s
+        ));
+    };
+    let parse = |v: &str| {
+        v.parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("'{}' is not a number", v))
+    };
+    Ok([parse(x)?, parse(y)?, parse(z)?])
 }
 
-fn two_perlin_spheres(rng: &mut rayst::Rng) -> HittableList {
-    let mut world = HittableList::default();
-    let pertext = Rc::new(NoiseTexture::new(rng, 4.0));
-
-    world.add(Rc::new(Sphere::new(
-        Point::y(-1000.0),
-        1000.0,
-        Rc::new(Lambertian::new_with_texture(pertext.clone())),
-    )));
-    world.add(Rc::new(Sphere::new(
-        Point::y(2.0),
-        2.0,
-        Rc::new(Lambertian::new_with_texture(pertext)),
-    )));
-    world
+fn parse_number(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("'{}' is not a number", s))
 }
 
-fn earth() -> HittableList {
-    let earth_texture = Rc::new(ImageTexture::new("earthmap.jpg").unwrap());
-    let earth_surface = Rc::new(Lambertian::new_with_texture(earth_texture));
-    let globe = Rc::new(Sphere::new(Point::default(), 2.0, earth_surface));
-
-    let mut world = HittableList::default();
-    world.add(globe);
-
-    world
+fn parse_vfov(s: &str) -> Result<f64, String> {
+    let v = parse_number(s)?;
+    if v <= 0.0 || v >= 180.0 {
+        return Err("field of view must be between 0 and 180 degrees".into());
+    }
+    Ok(v)
 }
 
-fn simple_light(rng: &mut rayst::Rng) -> HittableList {
-    let mut world = HittableList::default();
-
-    let pertext = Rc::new(NoiseTexture::new(rng, 4.0));
-    world.add(Rc::new(Sphere::new(
-        Point::y(-1000.0),
-        1000.0,
-        Rc::new(Lambertian::new_with_texture(pertext.clone())),
-    )));
-    world.add(Rc::new(Sphere::new(
-        Point::y(2.0),
-        2.0,
-        Rc::new(Lambertian::new_with_texture(pertext)),
-    )));
-
-    let difflight = Rc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
-    world.add(Rc::new(XYRect::new(3.0, 5.0, 1.0, 3.0, -2.0, difflight)));
-
-    world
+fn parse_positive(s: &str) -> Result<f64, String> {
+    let v = parse_number(s)?;
+    if v <= 0.0 {
+        return Err("must be positive".into());
+    }
+    Ok(v)
 }
 
-fn cornell_box() -> HittableList {
-    let mut world = HittableList::default();
-
-    let red = Rc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
-    let white = Rc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
-    let green = Rc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
-    let light = Rc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
-
-    world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
-    world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
-    world.add(Rc::new(XZRect::new(
-        213.0, 343.0, 227.0, 332.0, 554.0, light,
-    )));
-    world.add(Rc::new(XZRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        white.clone(),
-    )));
-    world.add(Rc::new(XZRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-    world.add(Rc::new(XYRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-
-    let mut box1: Rc<dyn Hittable> = Rc::new(Box3D::new(
-        Point::default(),
-        Point::new(165.0, 330.0, 165.0),
-        white.clone(),
-    ));
-    box1 = Rc::new(RotateY::new(box1, 15.0));
-    box1 = Rc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
-    world.add(box1);
-
-    let mut box2: Rc<dyn Hittable> = Rc::new(Box3D::new(
-        Point::default(),
-        Point::new(165.0, 165.0, 165.0),
-        white,
-    ));
-    box2 = Rc::new(RotateY::new(box2, -18.0));
-    box2 = Rc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
-    world.add(box2);
-
-    world
+fn parse_non_negative(s: &str) -> Result<f64, String> {
+    let v = parse_number(s)?;
+    if v < 0.0 {
+        return Err("must not be negative".into());
+    }
+    Ok(v)
 }
 
-fn cornell_smoke() -> HittableList {
-    let mut world = HittableList::default();
-
-    let red = Rc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
-    let white = Rc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
-    let green = Rc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
-    let light = Rc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
-
-    world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
-    world.add(Rc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
-    world.add(Rc::new(XZRect::new(
-        113.0, 443.0, 127.0, 432.0, 554.0, light,
-    )));
-    world.add(Rc::new(XZRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        white.clone(),
-    )));
-    world.add(Rc::new(XZRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-    world.add(Rc::new(XYRect::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
-    )));
-
-    let mut box1: Rc<dyn Hittable> = Rc::new(Box3D::new(
-        Point::default(),
-        Point::new(165.0, 330.0, 165.0),
-        white.clone(),
-    ));
-    box1 = Rc::new(RotateY::new(box1, 15.0));
-    box1 = Rc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
-    box1 = Rc::new(ConstantMedium::new(box1, 0.01, Color::new(0.0, 0.0, 0.0)));
-    world.add(box1);
-
-    let mut box2: Rc<dyn Hittable> = Rc::new(Box3D::new(
-        Point::default(),
-        Point::new(165.0, 165.0, 165.0),
-        white,
-    ));
-    box2 = Rc::new(RotateY::new(box2, -18.0));
-    box2 = Rc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
-    box2 = Rc::new(ConstantMedium::new(box2, 0.01, Color::new(1.0, 1.0, 1.0)));
-    world.add(box2);
-
-    world
+fn parse_bit_depth(s: &str) -> Result<u8, String> {
+    match s {
+        "8" => Ok(8),
+        "16" => Ok(16),
+        _ => Err("PNG bit depth must be 8 or 16".into()),
+    }
 }
 
-fn final_scene(rng: &mut rayst::Rng) -> HittableList {
-    let mut boxes1 = HittableList::default();
-    let ground = Rc::new(Lambertian::new(Color::new(0.48, 0.83, 0.53)));
-
-    let boxes_per_side = 20;
-    for i in 0..boxes_per_side {
-        for j in 0..boxes_per_side {
-            let w = 100.0;
-            let x0 = -1000.0 + i as f64 * w;
-            let z0 = -1000.0 + j as f64 * w;
-            let y0 = 0.0;
-            let x1 = x0 + w;
-            let y1 = rng.gen_range(1.0..101.0);
-            let z1 = z0 + w;
-
-            boxes1.add(Rc::new(Box3D::new(
-                Point::new(x0, y0, z0),
-                Point::new(x1, y1, z1),
-                ground.clone(),
-            )));
-        }
+fn parse_tonemap(s: &str) -> Result<String, String> {
+    match rayst::tonemap::by_name(s) {
+        Some(_) => Ok(s.to_owned()),
+        None => Err(format!(
+            "unknown tone mapping operator '{}', use clamp, reinhard, extended-reinhard, aces or hable",
+            s
+        )),
     }
+}
 
-    let mut world = HittableList::default();
-    world.add(Rc::new(BVHNode::new(rng, boxes1.as_slice_mut(), 0.0, 0.1)));
-
-    let light = Rc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
-    world.add(Rc::new(XZRect::new(
-        123.0, 423.0, 147.0, 412.0, 554.0, light,
-    )));
-
-    let center1 = Point::new(400.0, 400.0, 200.0);
-    let center2 = center1 + Vec3::x(30.0);
-    let moving_sphere_material = Rc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1)));
-    world.add(Rc::new(MovingSphere::new(
-        center1,
-        center2,
-        0.0..1.0,
-        50.0,
-        moving_sphere_material,
-    )));
-
-    world.add(Rc::new(Sphere::new(
-        Point::new(260.0, 150.0, 45.0),
-        50.0,
-        Rc::new(Dielectric::new(1.5)),
-    )));
-    world.add(Rc::new(Sphere::new(
-        Point::new(0.0, 150.0, 145.0),
-        50.0,
-        Rc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 1.0)),
-    )));
-
-    let boundary = Rc::new(Sphere::new(
-        Point::new(360.0, 150.0, 145.0),
-        70.0,
-        Rc::new(Dielectric::new(1.5)),
-    ));
-    world.add(boundary.clone());
-    world.add(Rc::new(ConstantMedium::new(
-        boundary,
-        0.2,
-        Color::new(0.2, 0.4, 0.9),
-    )));
-
-    let boundary = Rc::new(Sphere::new(
-        Point::default(),
-        5000.0,
-        Rc::new(Dielectric::new(1.5)),
-    ));
-    world.add(Rc::new(ConstantMedium::new(
-        boundary,
-        0.0001,
-        Color::new(1.0, 1.0, 1.0),
-    )));
-
-    let emat = Rc::new(Lambertian::new_with_texture(Rc::new(
-        ImageTexture::new("earthmap.jpg").unwrap(),
-    )));
-    world.add(Rc::new(Sphere::new(
-        Point::new(400.0, 200.0, 400.0),
-        100.0,
-        emat,
-    )));
-
-    let pertext = Rc::new(NoiseTexture::new(rng, 0.1));
-    world.add(Rc::new(Sphere::new(
-        Point::new(220.0, 280.0, 300.0),
-        80.0,
-        Rc::new(Lambertian::new_with_texture(pertext)),
-    )));
-
-    let mut boxes2 = HittableList::default();
-    let white = Rc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
-    for _ in 0..1000 {
-        boxes2.add(Rc::new(Sphere::new(
-            Point::new(
-                rng.gen_range(0.0..165.0),
-                rng.gen_range(0.0..165.0),
-                rng.gen_range(0.0..165.0),
-            ),
-            10.0,
-            white.clone(),
-        )));
-    }
+fn parse_sampler(s: &str) -> Result<SamplerKind, String> {
+    SamplerKind::by_name(s).ok_or_else(|| {
+        format!(
+            "unknown sampler '{}', use independent, stratified, halton, sobol or blue-noise",
+            s
+        )
+    })
+}
 
-    world.add(Rc::new(Translate::new(
-        Rc::new(RotateY::new(
-            Rc::new(BVHNode::new(rng, boxes2.as_slice_mut(), 0.0, 1.0)),
-            15.0,
-        )),
-        Vec3::new(-100.0, 270.0, 395.0),
-    )));
+fn parse_filter(s: &str) -> Result<FilterKind, String> {
+    FilterKind::by_name(s).ok_or_else(|| {
+        format!(
+            "unknown filter '{}', use box, tent, gaussian, mitchell or lanczos",
+            s
+        )
+    })
+}
 
-    world
+fn parse_aov(s: &str) -> Result<Aov, String> {
+    Aov::by_name(s).ok_or_else(|| {
+        format!(
+            "unknown AOV '{}', use depth, normal, albedo, position, uv, object_id, material_id or alpha",
+            s
+        )
+    })
 }
@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
     aabb::AABB,
@@ -17,10 +17,10 @@ pub struct Box3D {
 }
 
 impl Box3D {
-    pub fn new(p0: Point, p1: Point, material: Rc<dyn Material>) -> Self {
+    pub fn new(p0: Point, p1: Point, material: Arc<dyn Material>) -> Self {
         let mut sides = HittableList::default();
 
-        sides.add(Rc::new(XYRect::new(
+        sides.add(Arc::new(XYRect::new(
             p0.x,
             p1.x,
             p0.y,
@@ -28,7 +28,7 @@ impl Box3D {
             p0.z,
             material.clone(),
         )));
-        sides.add(Rc::new(XYRect::new(
+        sides.add(Arc::new(XYRect::new(
             p0.x,
             p1.x,
             p0.y,
@@ -37,7 +37,7 @@ impl Box3D {
             material.clone(),
         )));
 
-        sides.add(Rc::new(XZRect::new(
+        sides.add(Arc::new(XZRect::new(
             p0.x,
             p1.x,
             p0.z,
@@ -45,7 +45,7 @@ impl Box3D {
             p0.y,
             material.clone(),
         )));
-        sides.add(Rc::new(XZRect::new(
+        sides.add(Arc::new(XZRect::new(
             p0.x,
             p1.x,
             p0.z,
@@ -54,7 +54,7 @@ impl Box3D {
             material.clone(),
         )));
 
-        sides.add(Rc::new(YZRect::new(
+        sides.add(Arc::new(YZRect::new(
             p0.y,
             p1.y,
             p0.z,
@@ -62,7 +62,7 @@ impl Box3D {
             p0.x,
             material.clone(),
         )));
-        sides.add(Rc::new(YZRect::new(
+        sides.add(Arc::new(YZRect::new(
             p0.y,
             p1.y,
             p0.z,
@@ -84,7 +84,7 @@ impl Hittable for Box3D {
         Some(AABB::new(self.min, self.max))
     }
 
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         self.sides.hit(r, t_min, t_max)
     }
 }
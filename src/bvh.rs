@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, rc::Rc};
+use std::{cmp::Ordering, error::Error, fmt, sync::Arc};
 
 use rand::Rng;
 
@@ -7,22 +7,78 @@ use crate::{
     hittable::{HitRecord, Hittable},
     hittable_list::HittableList,
     ray::Ray,
+    vec3::{Point, Vec3},
 };
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum BVHError {
+    // Empty means there are no objects to build a hierarchy of.
+    Empty,
+    // InvalidBounds means an object has a bounding box with NaN or inverted coordinates.
+    InvalidBounds,
+}
+
+impl fmt::Display for BVHError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "bvh must contain objects"),
+            Self::InvalidBounds => write!(f, "object has an invalid bounding box"),
+        }
+    }
+}
+
+impl Error for BVHError {}
+
 // BVH stays for Bounded Volume Hierarchy.
+// Objects without a bounding box are kept aside by the root and tested for every ray.
 pub struct BVHNode {
-    pub left: Rc<dyn Hittable>,
-    pub right: Rc<dyn Hittable>,
+    pub left: Arc<dyn Hittable>,
+    pub right: Arc<dyn Hittable>,
     pub out_box: AABB,
+    pub unbounded: Vec<Arc<dyn Hittable>>,
 }
 
 impl BVHNode {
-    pub fn from_hittable_list(mut hlist: HittableList, time0: f64, time1: f64) -> Self {
-        let mut rng = crate::thread_rng();
-        Self::new(&mut rng, hlist.as_slice_mut(), time0, time1)
+    /// MAX_LEAF_SIZE is the default number of objects a SAH leaf may contain.
+    pub const MAX_LEAF_SIZE: usize = 4;
+
+    pub fn from_hittable_list(
+        mut hlist: HittableList,
+        time0: f64,
+        time1: f64,
+    ) -> Result<Self, BVHError> {
+        Self::new_sah(hlist.as_slice_mut(), time0, time1, Self::MAX_LEAF_SIZE)
     }
 
-    pub fn new(rng: &mut crate::Rng, lst: &mut [Rc<dyn Hittable>], time0: f64, time1: f64) -> Self {
+    /// new builds a tree splitting objects at the median along a random axis.
+    pub fn new(
+        rng: &mut crate::Rng,
+        lst: &[Arc<dyn Hittable>],
+        time0: f64,
+        time1: f64,
+    ) -> Result<Self, BVHError> {
+        let (prims, unbounded) = primitives(lst, time0, time1)?;
+        let mut bounded: Vec<_> = prims.into_iter().map(|p| p.object).collect();
+        let root = if bounded.is_empty() {
+            Self::empty()
+        } else {
+            Self::random(rng, &mut bounded, time0, time1)
+        };
+        Ok(Self { unbounded, ..root })
+    }
+
+    /// empty is the root of a tree whose objects are all unbounded.
+    fn empty() -> Self {
+        let empty: Arc<dyn Hittable> = Arc::new(HittableList::default());
+        Self {
+            left: empty.clone(),
+            right: empty,
+            out_box: AABB::new(Point::default(), Point::default()),
+            unbounded: Vec::new(),
+        }
+    }
+
+    fn random(rng: &mut crate::Rng, lst: &mut [Arc<dyn Hittable>], time0: f64, time1: f64) -> Self {
         let comparator = match rng.gen_range(0..=2) as u32 {
             0 => Self::compare::<0>,
             1 => Self::compare::<1>,
@@ -41,8 +97,8 @@ impl BVHNode {
             lst.sort_by(comparator);
             let (l, r) = lst.split_at_mut(lst.len() / 2);
             (
-                Rc::new(Self::new(rng, l, time0, time1)) as Rc<dyn Hittable>,
-                Rc::new(Self::new(rng, r, time0, time1)) as Rc<dyn Hittable>,
+                Arc::new(Self::random(rng, l, time0, time1)) as Arc<dyn Hittable>,
+                Arc::new(Self::random(rng, r, time0, time1)) as Arc<dyn Hittable>,
             )
         };
 
@@ -53,27 +109,255 @@ impl BVHNode {
             left,
             right,
             out_box: AABB::union(&box_left.unwrap(), &box_right.unwrap()),
+            unbounded: Vec::new(),
         }
     }
 
-    pub fn compare<const AXIS: usize>(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>) -> Ordering {
-        let box_a = a.bounding_box(0.0, 0.0).unwrap();
-        let box_b = b.bounding_box(0.0, 0.0).unwrap();
-        if box_a.min[AXIS] < box_b.min[AXIS] {
-            Ordering::Less
+    /// new_sah builds a tree using the binned Surface Area Heuristic.
+    /// Leaves hold up to `max_leaf_size` objects when it is cheaper than splitting them further.
+    pub fn new_sah(
+        lst: &[Arc<dyn Hittable>],
+        time0: f64,
+        time1: f64,
+        max_leaf_size: usize,
+    ) -> Result<Self, BVHError> {
+        let (mut prims, unbounded) = primitives(lst, time0, time1)?;
+        let root = if prims.is_empty() {
+            Self::empty()
         } else {
-            Ordering::Greater
+            Self::split_sah(&mut prims, max_leaf_size.max(1))
+        };
+        Ok(Self { unbounded, ..root })
+    }
+
+    fn build_sah(prims: &mut [Primitive], max_leaf_size: usize) -> Arc<dyn Hittable> {
+        if prims.len() == 1 {
+            return prims[0].object.clone();
+        }
+        if is_leaf(prims, &bounds(prims), max_leaf_size) {
+            let mut leaf = HittableList::default();
+            for p in prims.iter() {
+                leaf.add(p.object.clone());
+            }
+            return Arc::new(leaf);
         }
+        Arc::new(Self::split_sah(prims, max_leaf_size))
+    }
+
+    fn split_sah(prims: &mut [Primitive], max_leaf_size: usize) -> Self {
+        let out_box = bounds(prims);
+        if prims.len() == 1 {
+            let obj = prims[0].object.clone();
+            return Self {
+                left: obj.clone(),
+                right: obj,
+                out_box,
+                unbounded: Vec::new(),
+            };
+        }
+
+        let (mid, _) = partition(prims, &out_box);
+        let (l, r) = prims.split_at_mut(mid);
+        Self {
+            left: Self::build_sah(l, max_leaf_size),
+            right: Self::build_sah(r, max_leaf_size),
+            out_box,
+            unbounded: Vec::new(),
+        }
+    }
+
+    /// compare orders objects by the minimum of their boxes, unbounded ones go first.
+    pub fn compare<const AXIS: usize>(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>) -> Ordering {
+        let min_a = a.bounding_box(0.0, 0.0).map(|b| b.min[AXIS]);
+        let min_b = b.bounding_box(0.0, 0.0).map(|b| b.min[AXIS]);
+        min_a.partial_cmp(&min_b).unwrap_or(Ordering::Equal)
+    }
+}
+
+const BINS: usize = 12;
+// TRAVERSAL_COST is the cost of a box test relative to an object intersection.
+const TRAVERSAL_COST: f64 = 0.125;
+
+struct Primitive {
+    object: Arc<dyn Hittable>,
+    bbox: AABB,
+    centroid: Point,
+}
+
+impl Primitive {
+    fn centroid_box(&self) -> AABB {
+        AABB::new(self.centroid, self.centroid)
+    }
+}
+
+type Unbounded = Vec<Arc<dyn Hittable>>;
+
+/// primitives separates bounded objects from unbounded ones and validates their boxes.
+fn primitives(
+    lst: &[Arc<dyn Hittable>],
+    time0: f64,
+    time1: f64,
+) -> Result<(Vec<Primitive>, Unbounded), BVHError> {
+    if lst.is_empty() {
+        return Err(BVHError::Empty);
+    }
+
+    let mut prims = Vec::with_capacity(lst.len());
+    let mut unbounded = Vec::new();
+    for obj in lst {
+        let Some(bbox) = obj.bounding_box(time0, time1) else {
+            unbounded.push(obj.clone());
+            continue;
+        };
+        // Comparisons with NaN are false, so they are rejected as well.
+        if !(0..3).all(|a| bbox.min[a] <= bbox.max[a]) {
+            return Err(BVHError::InvalidBounds);
+        }
+        prims.push(Primitive {
+            object: obj.clone(),
+            centroid: bbox.centroid(),
+            bbox,
+        });
+    }
+    Ok((prims, unbounded))
+}
+
+fn bounds(prims: &[Primitive]) -> AABB {
+    prims[1..]
+        .iter()
+        .fold(prims[0].bbox.clone(), |acc, p| acc.union(&p.bbox))
+}
+
+fn centroid_bounds(prims: &[Primitive]) -> AABB {
+    prims.iter().fold(prims[0].centroid_box(), |acc, p| {
+        acc.union(&p.centroid_box())
+    })
+}
+
+fn bin_index(centroids: &AABB, axis: usize, c: f64) -> usize {
+    let extent = centroids.max[axis] - centroids.min[axis];
+    let i = (BINS as f64 * (c - centroids.min[axis]) / extent) as usize;
+    i.min(BINS - 1)
+}
+
+/// find_split returns the axis, the first bin of the right child and the cost of the cheapest split.
+fn find_split(prims: &[Primitive], bbox: &AABB) -> Option<(usize, usize, f64)> {
+    let centroids = centroid_bounds(prims);
+    let area = bbox.surface_area();
+    let mut best: Option<(usize, usize, f64)> = None;
+
+    for axis in 0..3 {
+        if centroids.max[axis] - centroids.min[axis] <= 0.0 {
+            continue;
+        }
+
+        let mut bins: [(usize, Option<AABB>); BINS] = Default::default();
+        for p in prims {
+            let bin = &mut bins[bin_index(&centroids, axis, p.centroid[axis])];
+            bin.0 += 1;
+            bin.1 = Some(match &bin.1 {
+                Some(b) => b.union(&p.bbox),
+                None => p.bbox.clone(),
+            });
+        }
+
+        // Sweep from the right to get the area and count of every right child.
+        let mut right = [(0, 0.0); BINS];
+        let mut count = 0;
+        let mut acc: Option<AABB> = None;
+        for i in (1..BINS).rev() {
+            count += bins[i].0;
+            acc = union_opt(acc, &bins[i].1);
+            right[i] = (count, acc.as_ref().map_or(0.0, AABB::surface_area));
+        }
+
+        let mut count = 0;
+        let mut acc: Option<AABB> = None;
+        for split in 1..BINS {
+            count += bins[split - 1].0;
+            acc = union_opt(acc, &bins[split - 1].1);
+            let (right_count, right_area) = right[split];
+            if count == 0 || right_count == 0 {
+                continue;
+            }
+
+            let left_area = acc.as_ref().map_or(0.0, AABB::surface_area);
+            let cost = TRAVERSAL_COST
+                + (count as f64 * left_area + right_count as f64 * right_area) / area;
+            if best.is_none_or(|(_, _, c)| cost < c) {
+                best = Some((axis, split, cost));
+            }
+        }
+    }
+
+    best
+}
+
+/// is_leaf reports whether intersecting all objects is cheaper than any split.
+fn is_leaf(prims: &[Primitive], bbox: &AABB, max_leaf_size: usize) -> bool {
+    prims.len() == 1
+        || prims.len() <= max_leaf_size
+            && find_split(prims, bbox).is_none_or(|(_, _, cost)| cost >= prims.len() as f64)
+}
+
+/// partition reorders objects by the best SAH split and returns its position and axis.
+fn partition(prims: &mut [Primitive], bbox: &AABB) -> (usize, usize) {
+    let centroids = centroid_bounds(prims);
+    if let Some((axis, split, _)) = find_split(prims, bbox) {
+        let mut mid = 0;
+        for i in 0..prims.len() {
+            if bin_index(&centroids, axis, prims[i].centroid[axis]) < split {
+                prims.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid != 0 && mid != prims.len() {
+            return (mid, axis);
+        }
+    }
+
+    // All centroids are in the same bin.
+    split_median(prims)
+}
+
+/// split_median splits objects in half along the widest axis of their centroids.
+fn split_median(prims: &mut [Primitive]) -> (usize, usize) {
+    let centroids = centroid_bounds(prims);
+    let d = centroids.max - centroids.min;
+    let axis = if d.x > d.y && d.x > d.z {
+        0
+    } else if d.y > d.z {
+        1
+    } else {
+        2
+    };
+    let mid = prims.len() / 2;
+    prims.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    (mid, axis)
+}
+
+fn union_opt(acc: Option<AABB>, b: &Option<AABB>) -> Option<AABB> {
+    match (acc, b) {
+        (Some(a), Some(b)) => Some(a.union(b)),
+        (a, b) => a.or_else(|| b.clone()),
     }
 }
 
 impl Hittable for BVHNode {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        if !self.out_box.hit(r, t_min, t_max) {
-            return None;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut rec = None;
+        for obj in self.unbounded.iter() {
+            if let Some(hit) = obj.hit(r, t_min, closest) {
+                closest = hit.t;
+                rec = Some(hit);
+            }
+        }
+        if !self.out_box.hit(r, t_min, closest) {
+            return rec;
         }
-        match self.left.hit(r, t_min, t_max) {
-            None => self.right.hit(r, t_min, t_max),
+        match self.left.hit(r, t_min, closest) {
+            None => self.right.hit(r, t_min, closest).or(rec),
             Some(hr_left) => match self.right.hit(r, t_min, hr_left.t) {
                 None => Some(hr_left),
                 opt_right => opt_right,
@@ -82,6 +366,330 @@ impl Hittable for BVHNode {
     }
 
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
         Some(self.out_box.clone())
     }
 }
+
+/// LinearBVH is a SAH hierarchy flattened into an array of nodes in depth-first order,
+/// so the first child of a node is always the next one.
+/// Objects without a bounding box (e.g. infinite planes) are kept aside and tested for every ray.
+pub struct LinearBVH {
+    nodes: Vec<LinearNode>,
+    objects: Vec<Arc<dyn Hittable>>,
+    unbounded: Vec<Arc<dyn Hittable>>,
+}
+
+/// LinearNode is either a leaf with `count` objects starting at `offset`,
+/// or an interior node with the second child at `offset`, split along `axis`.
+#[derive(Clone, Copy)]
+struct LinearNode {
+    min: [f32; 3],
+    max: [f32; 3],
+    offset: u32,
+    count: u16,
+    axis: u8,
+}
+
+const STACK_SIZE: usize = 64;
+
+const _: () = assert!(std::mem::size_of::<LinearNode>() == 32);
+
+impl LinearNode {
+    fn new(bbox: &AABB) -> Self {
+        // Round bounds outwards so boxes never shrink.
+        let down = |x: f64| {
+            let y = x as f32;
+            if y as f64 > x {
+                y.next_down()
+            } else {
+                y
+            }
+        };
+        let up = |x: f64| {
+            let y = x as f32;
+            if (y as f64) < x {
+                y.next_up()
+            } else {
+                y
+            }
+        };
+        Self {
+            min: [down(bbox.min.x), down(bbox.min.y), down(bbox.min.z)],
+            max: [up(bbox.max.x), up(bbox.max.y), up(bbox.max.z)],
+            offset: 0,
+            count: 0,
+            axis: 0,
+        }
+    }
+
+    fn bbox(&self) -> AABB {
+        AABB::new(
+            Point::new(self.min[0] as f64, self.min[1] as f64, self.min[2] as f64),
+            Point::new(self.max[0] as f64, self.max[1] as f64, self.max[2] as f64),
+        )
+    }
+}
+
+impl LinearBVH {
+    pub fn from_hittable_list(
+        mut hlist: HittableList,
+        time0: f64,
+        time1: f64,
+    ) -> Result<Self, BVHError> {
+        Self::new(hlist.as_slice_mut(), time0, time1, BVHNode::MAX_LEAF_SIZE)
+    }
+
+    pub fn new(
+        lst: &[Arc<dyn Hittable>],
+        time0: f64,
+        time1: f64,
+        max_leaf_size: usize,
+    ) -> Result<Self, BVHError> {
+        let (mut prims, unbounded) = primitives(lst, time0, time1)?;
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * prims.len()),
+            objects: Vec::with_capacity(prims.len()),
+            unbounded,
+        };
+        if !prims.is_empty() {
+            let max_leaf_size = max_leaf_size.clamp(1, u16::MAX as usize);
+            bvh.build(&mut prims, max_leaf_size, 0);
+        }
+        Ok(bvh)
+    }
+
+    /// build appends nodes for `prims` and returns the index of the root.
+    fn build(&mut self, prims: &mut [Primitive], max_leaf_size: usize, depth: usize) -> usize {
+        let bbox = bounds(prims);
+        let index = self.nodes.len();
+        self.nodes.push(LinearNode::new(&bbox));
+
+        if is_leaf(prims, &bbox, max_leaf_size) {
+            self.nodes[index].offset = self.objects.len() as u32;
+            self.nodes[index].count = prims.len() as u16;
+            self.objects.extend(prims.iter().map(|p| p.object.clone()));
+            return index;
+        }
+
+        // Deep trees fall back to median splits, which keeps the depth
+        // below the size of the traversal stack.
+        let (mid, axis) = if depth < STACK_SIZE / 2 {
+            partition(prims, &bbox)
+        } else {
+            split_median(prims)
+        };
+        let (l, r) = prims.split_at_mut(mid);
+        self.build(l, max_leaf_size, depth + 1);
+        let right = self.build(r, max_leaf_size, depth + 1);
+        self.nodes[index].offset = right as u32;
+        self.nodes[index].axis = axis as u8;
+        index
+    }
+}
+
+impl Hittable for LinearBVH {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut rec = None;
+        for obj in self.unbounded.iter() {
+            if let Some(hit) = obj.hit(r, t_min, closest) {
+                closest = hit.t;
+                rec = Some(hit);
+            }
+        }
+        if self.nodes.is_empty() {
+            return rec;
+        }
+
+        let inv_dir = Vec3::new(r.dir.x.recip(), r.dir.y.recip(), r.dir.z.recip());
+        let dir_is_neg = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            if node.bbox().hit_inv(r.orig, inv_dir, t_min, closest) {
+                if node.count > 0 {
+                    let start = node.offset as usize;
+                    for obj in &self.objects[start..start + node.count as usize] {
+                        if let Some(hit) = obj.hit(r, t_min, closest) {
+                            closest = hit.t;
+                            rec = Some(hit);
+                        }
+                    }
+                } else {
+                    // Visit the child closer to the ray origin first, so that the far one
+                    // can often be culled by the closest hit found so far.
+                    let (near, far) = if dir_is_neg[node.axis as usize] {
+                        (node.offset as usize, index + 1)
+                    } else {
+                        (index + 1, node.offset as usize)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    index = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            index = stack[stack_len];
+        }
+
+        rec
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.nodes.first().map(LinearNode::bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        color::Color,
+        material::{Lambertian, Material},
+        sphere::Sphere,
+        vec3::Vec3,
+    };
+
+    /// Floor is the unbounded plane y = -10.
+    struct Floor(Arc<dyn Material>);
+
+    impl Hittable for Floor {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+            let t = (-10.0 - r.orig.y) / r.dir.y;
+            if !(t_min..t_max).contains(&t) {
+                return None;
+            }
+            let p = r.at(t);
+            Some(HitRecord::new(
+                t,
+                p,
+                r,
+                p.x,
+                p.z,
+                self.0.as_ref(),
+                Vec3::y(1.0),
+            ))
+        }
+
+        fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+            None
+        }
+    }
+
+    fn spheres(rng: &mut crate::Rng) -> HittableList {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::default();
+        for _ in 0..200 {
+            let center = Point::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            );
+            list.add(Arc::new(Sphere::new(
+                center,
+                rng.gen_range(0.1..1.0),
+                material.clone(),
+            )));
+        }
+        list
+    }
+
+    fn assert_same_hits(rng: &mut crate::Rng, list: &HittableList, bvh: &dyn Hittable) {
+        for _ in 0..1000 {
+            let dir = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            let r = Ray::new(Point::new(0.0, 0.0, 20.0), dir, 0.0);
+            let expected = list.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            let actual = bvh.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn sah_matches_list() {
+        let mut rng = crate::Rng::seed_from_u64(42);
+        let mut list = spheres(&mut rng);
+        for max_leaf_size in [1, 4] {
+            let bvh = BVHNode::new_sah(list.as_slice_mut(), 0.0, 0.0, max_leaf_size).unwrap();
+            assert_same_hits(&mut rng, &list, &bvh);
+        }
+    }
+
+    #[test]
+    fn linear_matches_list() {
+        let mut rng = crate::Rng::seed_from_u64(42);
+        let mut list = spheres(&mut rng);
+        for max_leaf_size in [1, 4] {
+            let bvh = LinearBVH::new(list.as_slice_mut(), 0.0, 0.0, max_leaf_size).unwrap();
+            assert_same_hits(&mut rng, &list, &bvh);
+        }
+    }
+
+    #[test]
+    fn unbounded() {
+        let mut rng = crate::Rng::seed_from_u64(42);
+        let mut list = spheres(&mut rng);
+        list.add(Arc::new(Floor(Arc::new(Lambertian::new(Color::default())))));
+
+        let bvh = LinearBVH::new(list.as_slice_mut(), 0.0, 0.0, 4).unwrap();
+        assert!(bvh.bounding_box(0.0, 0.0).is_none());
+        assert_same_hits(&mut rng, &list, &bvh);
+
+        let bvh = BVHNode::new_sah(list.as_slice_mut(), 0.0, 0.0, 4).unwrap();
+        assert!(bvh.bounding_box(0.0, 0.0).is_none());
+        assert_same_hits(&mut rng, &list, &bvh);
+
+        let bvh = BVHNode::new(&mut rng, list.as_slice_mut(), 0.0, 0.0).unwrap();
+        assert!(bvh.bounding_box(0.0, 0.0).is_none());
+        assert_same_hits(&mut rng, &list, &bvh);
+
+        // A tree of unbounded objects only.
+        let floor: [Arc<dyn Hittable>; 1] =
+            [Arc::new(Floor(Arc::new(Lambertian::new(Color::default()))))];
+        let r = Ray::new(Point::default(), Vec3::y(-1.0), 0.0);
+        let bvh = BVHNode::new(&mut rng, &floor, 0.0, 0.0).unwrap();
+        assert_eq!(
+            bvh.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t),
+            Some(10.0)
+        );
+        let bvh = BVHNode::new_sah(&floor, 0.0, 0.0, 4).unwrap();
+        assert_eq!(
+            bvh.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t),
+            Some(10.0)
+        );
+    }
+
+    #[test]
+    fn degenerate() {
+        assert_eq!(
+            LinearBVH::new(&[], 0.0, 0.0, 4).err(),
+            Some(BVHError::Empty)
+        );
+
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let nan: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::x(f64::NAN), 1.0, material));
+        assert_eq!(
+            LinearBVH::new(&[nan], 0.0, 0.0, 4).err(),
+            Some(BVHError::InvalidBounds)
+        );
+    }
+}
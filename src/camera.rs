@@ -2,11 +2,10 @@ use std::ops::Range;
 
 use crate::{
     ray::Ray,
-    vec3::{random_in_unit_disc, Point, Vec3},
+    sampler::Sampler,
+    vec3::{sample_unit_disc, Onb, Point, Vec3},
 };
 
-use rand::Rng;
-
 pub struct Camera {
     origin: Point,
     lower_left_corner: Point,
@@ -14,7 +13,6 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
     lens_radius: f64,
     time: Range<f64>,
 }
@@ -35,6 +33,7 @@ impl Default for Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Point,
         look_at: Point,
@@ -49,9 +48,7 @@ impl Camera {
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let w = (look_from - look_at).normalize();
-        let u = vup.cross(w).normalize();
-        let v = w.cross(u);
+        let Onb { u, v, w } = Onb::from_w_up(look_from - look_at, vup);
 
         let origin = look_from;
         let horizontal = focus_dist * viewport_width * u;
@@ -66,7 +63,6 @@ impl Camera {
             vertical,
             u,
             v,
-            w,
             lens_radius,
             time,
         }
@@ -74,8 +70,10 @@ impl Camera {
 }
 
 impl Camera {
-    pub fn get_ray(&self, r: &mut crate::Rng, u: f64, v: f64) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disc(r);
+    /// get_ray returns the ray through (`u`, `v`) on the viewport. The point on the lens and
+    /// the time are drawn from the next three dimensions of `sampler`.
+    pub fn get_ray(&self, sampler: &mut dyn Sampler, u: f64, v: f64) -> Ray {
+        let rd = self.lens_radius * sample_unit_disc(sampler.get_2d());
         let offset = self.u * rd.x + self.v * rd.y;
 
         Ray {
@@ -83,7 +81,8 @@ impl Camera {
             dir: self.lower_left_corner + u * self.horizontal + v * self.vertical
                 - self.origin
                 - offset,
-            time: r.gen_range(self.time.clone()),
+            time: self.time.start + sampler.get_1d() * (self.time.end - self.time.start),
+            sample: 0.0,
         }
     }
 }
@@ -0,0 +1,252 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    aov::Aov,
+    color::Color,
+    film::{Film, Pixel},
+};
+
+/// MAGIC starts checkpoint files, the last byte is the version of the format.
+const MAGIC: &[u8; 8] = b"RAYSTCK3";
+
+/// MAX_PIXELS bounds the size of films read from checkpoints, so that corrupt headers
+/// fail instead of exhausting memory.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// CheckpointError is returned when a checkpoint can't be written or resumed.
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(PathBuf, io::Error),
+    Format(PathBuf),
+    // Mismatch means that the checkpoint was made for another scene or other settings.
+    Mismatch(PathBuf),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "checkpoint {}: {}", path.display(), err),
+            Self::Format(path) => write!(f, "{} is not a checkpoint", path.display()),
+            Self::Mismatch(path) => write!(
+                f,
+                "checkpoint {} was made for a different scene or render settings",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// fingerprint hashes everything that determines the image (FNV-1a), so that a checkpoint
+/// is only resumed by a render producing the same image.
+pub fn fingerprint(parts: &[&[u8]]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for part in parts {
+        // The length separates parts, so that moving bytes between them changes the hash.
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part.iter()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// save writes the accumulated samples of the film. The file is replaced atomically,
+/// so an interrupted save keeps the previous checkpoint.
+pub fn save(film: &Film, fingerprint: u64, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let io_error = |err| CheckpointError::Io(path.to_owned(), err);
+
+    let mut w = BufWriter::new(File::create(&tmp).map_err(io_error)?);
+    write_film(&mut w, film, fingerprint)
+        .and_then(|_| w.flush())
+        .map_err(io_error)?;
+    drop(w);
+    fs::rename(&tmp, path).map_err(io_error)
+}
+
+/// load reads a checkpoint written by `save` with the same fingerprint.
+pub fn load(path: impl AsRef<Path>, fingerprint: u64) -> Result<Film, CheckpointError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| CheckpointError::Io(path.to_owned(), err))?;
+    let mut r = BufReader::new(file);
+    // Files ending early are most likely not checkpoints.
+    let format_error = |err: io::Error| match err.kind() {
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData => {
+            CheckpointError::Format(path.to_owned())
+        }
+        _ => CheckpointError::Io(path.to_owned(), err),
+    };
+
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic).map_err(format_error)?;
+    if &magic != MAGIC {
+        return Err(CheckpointError::Format(path.to_owned()));
+    }
+    if read_u64(&mut r).map_err(format_error)? != fingerprint {
+        return Err(CheckpointError::Mismatch(path.to_owned()));
+    }
+    read_film(&mut r).map_err(format_error)
+}
+
+fn write_film(w: &mut impl Write, film: &Film, fingerprint: u64) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&fingerprint.to_le_bytes())?;
+    w.write_all(&film.width().to_le_bytes())?;
+    w.write_all(&film.height().to_le_bytes())?;
+    for px in film.pixels() {
+        for c in [px.mean, px.m2] {
+            for x in [c.r(), c.g(), c.b()] {
+                w.write_all(&x.to_le_bytes())?;
+            }
+        }
+        w.write_all(&px.samples.to_le_bytes())?;
+    }
+    // AOVs are stored by their position in Aov::ALL.
+    w.write_all(&(film.aovs().len() as u32).to_le_bytes())?;
+    for aov in film.aovs() {
+        let index = Aov::ALL.iter().position(|a| a == aov).unwrap() as u32;
+        w.write_all(&index.to_le_bytes())?;
+    }
+    for c in film.splats().iter().chain(film.aov_values()) {
+        for x in [c.r(), c.g(), c.b()] {
+            w.write_all(&x.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_film(r: &mut impl Read) -> io::Result<Film> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let width = read_u32(r)?;
+    let height = read_u32(r)?;
+    let n = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|&n| n <= MAX_PIXELS)
+        .ok_or_else(|| invalid("film is too large"))?;
+    // Buffers grow while they are read, so truncated files fail early.
+    let pixels = (0..n)
+        .map(|_| {
+            let (mean, m2) = (read_color(r)?, read_color(r)?);
+            Ok(Pixel {
+                mean,
+                m2,
+                samples: read_u32(r)?,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+    let aov_count = read_u32(r)? as usize;
+    if aov_count > Aov::ALL.len() {
+        return Err(invalid("too many AOVs"));
+    }
+    let aovs = (0..aov_count)
+        .map(|_| {
+            let index = read_u32(r)? as usize;
+            Aov::ALL
+                .get(index)
+                .copied()
+                .ok_or_else(|| invalid("unknown AOV"))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let splats = (0..n).map(|_| read_color(r)).collect::<io::Result<_>>()?;
+    let aov_values = (0..n * aovs.len())
+        .map(|_| read_color(r))
+        .collect::<io::Result<_>>()?;
+    Ok(Film::from_pixels(
+        width, height, pixels, splats, aovs, aov_values,
+    ))
+}
+
+fn read_color(r: &mut impl Read) -> io::Result<Color> {
+    let [red, green, blue] = [read_u64(r)?, read_u64(r)?, read_u64(r)?].map(f64::from_bits);
+    Ok(Color::new(red, green, blue))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut film = Film::new(3, 2).with_aovs(&[Aov::Alpha, Aov::Normal]);
+        for (i, c) in [0.5, 2.0, 0.25].into_iter().enumerate() {
+            film.add_sample(i as u32, 1, Color::new(c, 1.0, 0.0));
+            film.add_sample(i as u32, 1, Color::new(1.0, c, 0.0));
+        }
+        film.add_splat((1.2, 0.7), Color::new(0.0, 0.0, 3.0));
+        let px = Pixel {
+            mean: Color::new(1.0, 1.0, 1.0),
+            m2: Color::default(),
+            samples: 1,
+        };
+        film.merge_pixel(
+            2,
+            0,
+            &px,
+            &[Color::new(1.0, 1.0, 1.0), Color::new(0.0, 1.0, 0.0)],
+        );
+        let path = std::env::temp_dir().join(format!("rayst-{}.checkpoint", std::process::id()));
+
+        let hash = fingerprint(&[b"scene", b"settings"]);
+        save(&film, hash, &path).unwrap();
+        let loaded = load(&path, hash).unwrap();
+        let mismatch = load(&path, fingerprint(&[b"scene", b"other settings"]));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(film.radiance(), loaded.radiance());
+        assert_eq!(film.sample_counts(), loaded.sample_counts());
+        assert_eq!(film.variance(1, 1), loaded.variance(1, 1));
+        assert_eq!(film.aovs(), loaded.aovs());
+        assert_eq!(film.aov(Aov::Normal), loaded.aov(Aov::Normal));
+        assert!(matches!(mismatch, Err(CheckpointError::Mismatch(_))));
+    }
+
+    #[test]
+    fn corrupt() {
+        let path =
+            std::env::temp_dir().join(format!("rayst-{}-corrupt.checkpoint", std::process::id()));
+        let hash = fingerprint(&[b"scene"]);
+        let header = |width: u32, height: u32| {
+            let mut data = MAGIC.to_vec();
+            data.extend(hash.to_le_bytes());
+            data.extend(width.to_le_bytes());
+            data.extend(height.to_le_bytes());
+            data
+        };
+        for data in [
+            header(u32::MAX, u32::MAX),
+            header(1 << 16, 1 << 12),
+            header(2, 2),
+        ] {
+            fs::write(&path, data).unwrap();
+            assert!(matches!(load(&path, hash), Err(CheckpointError::Format(_))));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, AddAssign, Mul};
+use std::ops::{Add, AddAssign, Div, Mul, Sub};
 
 use crate::vec3::Vec3;
 
@@ -7,7 +7,63 @@ use rand::{
     Rng,
 };
 
-#[derive(Clone, Copy, Debug, Default)]
+/// ColorSpace enumerates color spaces `Color` can be converted between.
+/// Colors are stored in linear sRGB (Rec. 709 primaries, D65 white point) unless stated otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    LinearSrgb,
+    /// sRGB primaries with the sRGB transfer function applied.
+    Srgb,
+    /// CIE 1931 XYZ, D65 white point.
+    Xyz,
+    /// ACEScg (AP1 primaries, D60 white point), linear.
+    AcesCg,
+}
+
+const LINEAR_SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4123907993, 0.3575843394, 0.1804807884],
+    [0.2126390059, 0.7151686788, 0.0721923154],
+    [0.0193308187, 0.1191947798, 0.9505321522],
+];
+
+const XYZ_TO_LINEAR_SRGB: [[f64; 3]; 3] = [
+    [3.2409699419, -1.5373831776, -0.4986107603],
+    [-0.9692436363, 1.8759675015, 0.0415550574],
+    [0.0556300797, -0.2039769589, 1.0569715142],
+];
+
+// Both ACEScg matrices include the Bradford chromatic adaptation between D65 and D60.
+const LINEAR_SRGB_TO_ACESCG: [[f64; 3]; 3] = [
+    [0.6130974024, 0.3395231462, 0.0473794514],
+    [0.0701937225, 0.9163538791, 0.0134523985],
+    [0.0206155929, 0.1095697729, 0.8698146342],
+];
+
+const ACESCG_TO_LINEAR_SRGB: [[f64; 3]; 3] = [
+    [1.7050509927, -0.6217921207, -0.0832588720],
+    [-0.1302564175, 1.1408047366, -0.0105483191],
+    [-0.0240033568, -0.1289689761, 1.1529723329],
+];
+
+/// srgb_to_linear decodes a single sRGB encoded component.
+pub fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// linear_to_srgb encodes a single linear component with the sRGB transfer function.
+pub fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(2.4_f64.recip()) - 0.055
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Color {
     r: f64,
     g: f64,
@@ -27,21 +83,99 @@ impl Color {
         }
     }
 
-    pub fn adjust_and_format(&self, samples: u32) -> String {
-        let scale = 1.0 / samples as f64;
+    pub fn r(&self) -> f64 {
+        self.r
+    }
+
+    pub fn g(&self) -> f64 {
+        self.g
+    }
+
+    pub fn b(&self) -> f64 {
+        self.b
+    }
+
+    /// luminance returns the relative luminance using Rec. 709 primaries.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn max_component(&self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// map applies `f` to every component.
+    pub fn map(&self, f: impl Fn(f64) -> f64) -> Color {
+        Color::new(f(self.r), f(self.g), f(self.b))
+    }
+
+    pub fn clamp(&self, min: f64, max: f64) -> Color {
+        self.map(|x| x.clamp(min, max))
+    }
+
+    /// to_srgb applies the sRGB transfer function to linear components, clamping them to [0, 1].
+    pub fn to_srgb(&self) -> Color {
+        self.map(|x| linear_to_srgb(x.clamp(0.0, 1.0)))
+    }
+
+    /// from_srgb converts sRGB encoded components to linear ones.
+    pub fn from_srgb(&self) -> Color {
+        self.map(srgb_to_linear)
+    }
+
+    pub fn to_xyz(&self) -> Color {
+        self.transform(&LINEAR_SRGB_TO_XYZ)
+    }
+
+    pub fn from_xyz(&self) -> Color {
+        self.transform(&XYZ_TO_LINEAR_SRGB)
+    }
+
+    pub fn to_acescg(&self) -> Color {
+        self.transform(&LINEAR_SRGB_TO_ACESCG)
+    }
+
+    pub fn from_acescg(&self) -> Color {
+        self.transform(&ACESCG_TO_LINEAR_SRGB)
+    }
 
-        // `.srqt()` stays for gamma correction with gamma=2.0
-        let r = (self.r * scale).sqrt();
-        let g = (self.g * scale).sqrt();
-        let b = (self.b * scale).sqrt();
+    /// convert interprets the color as being in space `from` and returns it in space `to`.
+    pub fn convert(&self, from: ColorSpace, to: ColorSpace) -> Color {
+        let linear = match from {
+            ColorSpace::LinearSrgb => *self,
+            ColorSpace::Srgb => self.from_srgb(),
+            ColorSpace::Xyz => self.from_xyz(),
+            ColorSpace::AcesCg => self.from_acescg(),
+        };
+        match to {
+            ColorSpace::LinearSrgb => linear,
+            ColorSpace::Srgb => linear.to_srgb(),
+            ColorSpace::Xyz => linear.to_xyz(),
+            ColorSpace::AcesCg => linear.to_acescg(),
+        }
+    }
 
-        format!(
-            "{} {} {}\n",
-            (256.0 * r.clamp(0.0, 0.999)) as u32,
-            (256.0 * g.clamp(0.0, 0.999)) as u32,
-            (256.0 * b.clamp(0.0, 0.999)) as u32
+    fn transform(&self, m: &[[f64; 3]; 3]) -> Color {
+        Color::new(
+            m[0][0] * self.r + m[0][1] * self.g + m[0][2] * self.b,
+            m[1][0] * self.r + m[1][1] * self.g + m[1][2] * self.b,
+            m[2][0] * self.r + m[2][1] * self.g + m[2][2] * self.b,
         )
     }
+
+    /// to_rgb8 quantizes components in [0, 1] to 8 bits.
+    pub fn to_rgb8(&self) -> [u8; 3] {
+        [self.r, self.g, self.b].map(|x| (256.0 * x.clamp(0.0, 0.999)) as u8)
+    }
+
+    /// to_rgb16 quantizes components in [0, 1] to 16 bits.
+    pub fn to_rgb16(&self) -> [u16; 3] {
+        [self.r, self.g, self.b].map(|x| (65536.0 * x.clamp(0.0, 0.99999)) as u16)
+    }
+
+    pub fn to_rgb32f(&self) -> [f32; 3] {
+        [self.r as f32, self.g as f32, self.b as f32]
+    }
 }
 
 impl Distribution<Color> for Standard {
@@ -71,6 +205,19 @@ impl AddAssign for Color {
     }
 }
 
+impl Sub for Color {
+    type Output = Color;
+
+    #[inline]
+    fn sub(self, other: Color) -> Color {
+        Color {
+            r: self.r - other.r,
+            g: self.g - other.g,
+            b: self.b - other.b,
+        }
+    }
+}
+
 impl Mul<Color> for f64 {
     type Output = Color;
 
@@ -109,3 +256,70 @@ impl Mul<Color> for Color {
         }
     }
 }
+
+impl Div<f64> for Color {
+    type Output = Color;
+
+    #[inline]
+    fn div(self, v: f64) -> Color {
+        Color {
+            r: self.r / v,
+            g: self.g / v,
+            b: self.b / v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-5;
+
+    fn assert_close(a: Color, b: Color) {
+        assert!(
+            (a.r - b.r).abs() < EPSILON
+                && (a.g - b.g).abs() < EPSILON
+                && (a.b - b.b).abs() < EPSILON,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn srgb_transfer() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < EPSILON);
+        assert!((srgb_to_linear(0.5) - 0.214041).abs() < EPSILON);
+        assert!((linear_to_srgb(0.214041) - 0.5).abs() < EPSILON);
+        assert!((linear_to_srgb(0.001) - 0.01292).abs() < EPSILON);
+    }
+
+    #[test]
+    fn conversions() {
+        let spaces = [
+            ColorSpace::LinearSrgb,
+            ColorSpace::Srgb,
+            ColorSpace::Xyz,
+            ColorSpace::AcesCg,
+        ];
+        let linear = ColorSpace::LinearSrgb;
+        let c = Color::new(0.8, 0.3, 0.05);
+        for from in spaces {
+            for to in spaces {
+                assert_close(
+                    c.convert(linear, from)
+                        .convert(from, to)
+                        .convert(to, linear),
+                    c,
+                );
+            }
+        }
+
+        // White stays white and Y is luminance.
+        let white = Color::new(1.0, 1.0, 1.0);
+        assert_close(white.to_acescg(), white);
+        assert!((c.to_xyz().g - c.luminance()).abs() < 1e-3);
+    }
+}
@@ -1,6 +1,4 @@
-use std::rc::Rc;
-
-use rand::Rng;
+use std::sync::Arc;
 
 use crate::{
     aabb::AABB,
@@ -8,31 +6,47 @@ use crate::{
     hittable::{HitRecord, Hittable},
     material::{Isotropic, Material},
     ray::Ray,
+    sampler::{hash, hashed_1d},
     texture::Texture,
     vec3::Vec3,
 };
 
+/// ConstantMedium is a medium of constant density inside a closed boundary. Rays scatter at an
+/// exponentially distributed distance drawn from `Ray::sample`.
 pub struct ConstantMedium {
-    boundary: Rc<dyn Hittable>,
-    phase: Rc<dyn Material>,
+    boundary: Arc<dyn Hittable>,
+    phase: Arc<dyn Material>,
     neg_inv_density: f64,
+    // seed decorrelates the distances of media crossed by the same ray,
+    // every medium of a scene should have its own.
+    seed: u64,
 }
 
 impl ConstantMedium {
-    pub fn new(boundary: Rc<dyn Hittable>, d: f64, c: Color) -> Self {
-        Self {
-            boundary,
-            phase: Rc::new(Isotropic::new(c)),
-            neg_inv_density: -d.recip(),
-        }
+    pub fn new(boundary: Arc<dyn Hittable>, d: f64, c: Color) -> Self {
+        Self::new_with_phase(boundary, d, Arc::new(Isotropic::new(c)))
+    }
+    pub fn new_with_texture(
+        boundary: Arc<dyn Hittable>,
+        d: f64,
+        texture: Arc<dyn Texture>,
+    ) -> Self {
+        Self::new_with_phase(boundary, d, Arc::new(Isotropic::new_with_texture(texture)))
     }
-    pub fn new_with_texture(boundary: Rc<dyn Hittable>, d: f64, texture: Rc<dyn Texture>) -> Self {
+
+    fn new_with_phase(boundary: Arc<dyn Hittable>, d: f64, phase: Arc<dyn Material>) -> Self {
         Self {
             boundary,
-            phase: Rc::new(Isotropic::new_with_texture(texture)),
+            phase,
             neg_inv_density: -d.recip(),
+            seed: 0,
         }
     }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
 }
 
 impl Hittable for ConstantMedium {
@@ -40,20 +54,12 @@ impl Hittable for ConstantMedium {
         self.boundary.bounding_box(time0, time1)
     }
 
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut rng = rand::thread_rng();
-        let enable_debug = false;
-        let debugging = enable_debug && rng.gen::<f64>() < 0.00001;
-
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         match self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY) {
             None => None,
             Some(mut rec) => match self.boundary.hit(r, rec.t + 0.0001, f64::INFINITY) {
                 None => None,
                 Some(rec2) => {
-                    if debugging {
-                        eprintln!("t_min={}, t_max={}", rec.t, rec2.t);
-                    }
-
                     let r1t = rec.t.max(t_min);
                     let r2t = rec2.t.min(t_max);
                     if r1t >= r2t {
@@ -64,23 +70,24 @@ impl Hittable for ConstantMedium {
 
                     let ray_length = r.dir.length();
                     let distance_inside_boundary = (r2t - rec.t) * ray_length;
-                    let hit_distance = self.neg_inv_density * rng.gen::<f64>().ln();
+                    // The origin of the ray in the space of the medium tells instances apart.
+                    let key = [r.orig.x, r.orig.y, r.orig.z]
+                        .iter()
+                        .fold(hash(self.seed, r.sample.to_bits()), |h, c| {
+                            hash(h, c.to_bits())
+                        });
+                    let u = hashed_1d(key);
+                    let hit_distance = self.neg_inv_density * (1.0 - u).ln();
                     if hit_distance > distance_inside_boundary {
                         return None;
                     }
 
                     rec.t += hit_distance / ray_length;
                     rec.p = r.at(rec.t);
-                    if debugging {
-                        eprintln!(
-                            "hit_distance={}\nrec.t={}\nrec.p={}\n",
-                            hit_distance, rec.t, rec.p
-                        );
-                    }
 
                     rec.normal = Vec3::x(1.0);
                     rec.front_face = true;
-                    rec.material = self.phase.clone();
+                    rec.material = self.phase.as_ref();
 
                     Some(rec)
                 }
@@ -88,3 +95,47 @@ impl Hittable for ConstantMedium {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, sphere::Sphere, vec3::Point};
+
+    #[test]
+    fn transmittance() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let boundary = Arc::new(Sphere::new(Point::default(), 1.0, material));
+        let medium = ConstantMedium::new(boundary, 0.5, Color::new(1.0, 1.0, 1.0));
+
+        // Rays through the center pass 2 units of density 0.5 unscattered with probability 1/e.
+        const N: usize = 20000;
+        let passed = (0..N)
+            .filter(|&i| {
+                let r = Ray::new(Point::z(-5.0), Vec3::z(1.0), 0.0)
+                    .with_sample((i as f64 + 0.5) / N as f64);
+                medium.hit(&r, 0.001, f64::INFINITY).is_none()
+            })
+            .count();
+        let expected = (-1.0f64).exp();
+        assert!((passed as f64 / N as f64 - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn seeds() {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let medium = |seed| {
+            let boundary = Arc::new(Sphere::new(Point::default(), 1.0, material.clone()));
+            ConstantMedium::new(boundary, 0.5, Color::new(1.0, 1.0, 1.0)).with_seed(seed)
+        };
+        let (a, b) = (medium(1), medium(2));
+
+        // Media with the same boundary scatter the same ray at different distances.
+        let r = Ray::new(Point::z(-5.0), Vec3::z(1.0), 0.0).with_sample(0.5);
+        let t = |m: &ConstantMedium, r: &Ray| m.hit(r, 0.001, f64::INFINITY).map(|rec| rec.t);
+        assert_ne!(t(&a, &r), t(&b, &r));
+
+        // So does a medium seen through two different origins along the same ray.
+        let moved = Ray::new(Point::z(-4.0), Vec3::z(1.0), 0.0).with_sample(0.5);
+        assert_ne!(t(&a, &r).map(|t| t - 1.0), t(&a, &moved));
+    }
+}
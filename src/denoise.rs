@@ -0,0 +1,279 @@
+use crate::{aov::Aov, color::Color, film::Film};
+
+/// Denoiser smooths rendered images with an edge-avoiding à-trous wavelet filter (Dammertz et
+/// al. 2010) guided by the variance of pixels, as in SVGF. Albedo and normal AOVs of the film
+/// stop it at texture and geometry edges when present.
+#[derive(Clone, Copy, Debug)]
+pub struct Denoiser {
+    // iterations is the number of filter passes, each doubling the distance between taps.
+    pub iterations: u32,
+    // sigma_luminance scales how many standard deviations luminance may differ.
+    pub sigma_luminance: f64,
+    // sigma_normal is the exponent of the cosine between normals.
+    pub sigma_normal: f64,
+    // sigma_albedo is how far albedos may differ.
+    pub sigma_albedo: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_luminance: 4.0,
+            sigma_normal: 128.0,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+// KERNEL is the B3 spline the filter spreads over 5x5 taps.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// MIN_ALBEDO is the albedo below which pixels are filtered without dividing it out.
+const MIN_ALBEDO: f64 = 0.01;
+
+/// Guides are the per-pixel features the filter preserves edges of.
+struct Guides {
+    normal: Option<Vec<Color>>,
+    albedo: Option<Vec<Color>>,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// denoise returns a copy of the film with the radiance of pixels, splats included,
+    /// replaced by the filtered one. Only the radiance of the copy is meant to be used.
+    pub fn denoise(&self, film: &Film) -> Film {
+        let (width, height) = (film.width() as usize, film.height() as usize);
+        let guides = Guides {
+            normal: film.aov(Aov::Normal),
+            albedo: film.aov(Aov::Albedo),
+        };
+
+        // Textures are divided out to be kept sharp, only the lighting is filtered.
+        let albedo: Vec<Color> = match &guides.albedo {
+            Some(albedo) => albedo
+                .iter()
+                .map(|a| a.map(|x| if x < MIN_ALBEDO { 1.0 } else { x }))
+                .collect(),
+            None => vec![Color::new(1.0, 1.0, 1.0); width * height],
+        };
+        let mut color: Vec<Color> = film
+            .radiance()
+            .iter()
+            .zip(&albedo)
+            .map(|(&c, &a)| Color::new(c.r() / a.r(), c.g() / a.g(), c.b() / a.b()))
+            .collect();
+        let mut variance = luminance_variance(film, &color, &albedo);
+
+        for i in 0..self.iterations {
+            let step = 1 << i;
+            let blurred = blur_3x3(&variance, width, height);
+            (color, variance) = self.pass(&color, &variance, &blurred, &guides, width, step);
+        }
+
+        let radiance = color.iter().zip(&albedo).map(|(&c, &a)| c * a).collect();
+        let mut denoised = film.clone();
+        denoised.replace_radiance(radiance);
+        denoised
+    }
+
+    /// pass filters with taps `step` pixels apart, it returns the color and its variance.
+    fn pass(
+        &self,
+        color: &[Color],
+        variance: &[f64],
+        blurred: &[f64],
+        guides: &Guides,
+        width: usize,
+        step: usize,
+    ) -> (Vec<Color>, Vec<f64>) {
+        let height = color.len() / width;
+        let mut out_color = Vec::with_capacity(color.len());
+        let mut out_variance = Vec::with_capacity(color.len());
+
+        for y in 0..height {
+            for x in 0..width {
+                let p = y * width + x;
+                let luminance = color[p].luminance();
+                let sigma = self.sigma_luminance * blurred[p].sqrt() + 1e-6;
+
+                let (mut sum, mut sum_variance, mut sum_weight) = (Color::default(), 0.0, 0.0);
+                for (j, hy) in KERNEL.iter().enumerate() {
+                    let qy = y as isize + (j as isize - 2) * step as isize;
+                    if qy < 0 || qy >= height as isize {
+                        continue;
+                    }
+                    for (i, hx) in KERNEL.iter().enumerate() {
+                        let qx = x as isize + (i as isize - 2) * step as isize;
+                        if qx < 0 || qx >= width as isize {
+                            continue;
+                        }
+                        let q = qy as usize * width + qx as usize;
+
+                        let w_luminance = (-(luminance - color[q].luminance()).abs() / sigma).exp();
+                        let weight = hx * hy * w_luminance * self.guide_weight(guides, p, q);
+                        sum += color[q] * weight;
+                        sum_variance += weight * weight * variance[q];
+                        sum_weight += weight;
+                    }
+                }
+                // The center tap has weight 3/8 * 3/8, so sum_weight is positive.
+                out_color.push(sum / sum_weight);
+                out_variance.push(sum_variance / (sum_weight * sum_weight));
+            }
+        }
+        (out_color, out_variance)
+    }
+
+    fn guide_weight(&self, guides: &Guides, p: usize, q: usize) -> f64 {
+        let mut weight = 1.0;
+        if let Some(normal) = &guides.normal {
+            let (a, b) = (normal[p], normal[q]);
+            let dot = a.r() * b.r() + a.g() * b.g() + a.b() * b.b();
+            let lengths = (dot_self(a) * dot_self(b)).sqrt();
+            if lengths > 0.0 {
+                weight *= (dot / lengths).max(0.0).powf(self.sigma_normal);
+            }
+        }
+        if let Some(albedo) = &guides.albedo {
+            let d = albedo[p] - albedo[q];
+            weight *= (-dot_self(d).sqrt() / self.sigma_albedo).exp();
+        }
+        weight
+    }
+}
+
+fn dot_self(c: Color) -> f64 {
+    c.r() * c.r() + c.g() * c.g() + c.b() * c.b()
+}
+
+/// luminance_variance estimates the variance of the luminance of `color`, the radiance of the
+/// film divided by `albedo`. Pixels with few samples use the variance of their neighborhood.
+fn luminance_variance(film: &Film, color: &[Color], albedo: &[Color]) -> Vec<f64> {
+    const MIN_SAMPLES: u32 = 4;
+
+    let (width, height) = (film.width() as usize, film.height() as usize);
+    let mut variance = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let p = y * width + x;
+            let samples = film.samples(x as u32, y as u32);
+            if samples >= MIN_SAMPLES {
+                // Variance of the mean, in the units of the filtered color.
+                let v = film.variance(x as u32, y as u32) / samples as f64;
+                let a = albedo[p];
+                let v = Color::new(
+                    v.r() / (a.r() * a.r()),
+                    v.g() / (a.g() * a.g()),
+                    v.b() / (a.b() * a.b()),
+                );
+                variance.push(v.luminance());
+                continue;
+            }
+
+            let (mut sum, mut sum_squares, mut n) = (0.0, 0.0, 0.0);
+            for qy in y.saturating_sub(2)..(y + 3).min(height) {
+                for qx in x.saturating_sub(2)..(x + 3).min(width) {
+                    let l = color[qy * width + qx].luminance();
+                    sum += l;
+                    sum_squares += l * l;
+                    n += 1.0;
+                }
+            }
+            let mean = sum / n;
+            variance.push((sum_squares / n - mean * mean).max(0.0));
+        }
+    }
+    variance
+}
+
+/// blur_3x3 smooths the variance with a small Gaussian to make edge stopping more robust.
+fn blur_3x3(values: &[f64], width: usize, height: usize) -> Vec<f64> {
+    const GAUSSIAN: [f64; 3] = [0.25, 0.5, 0.25];
+
+    let mut out = Vec::with_capacity(values.len());
+    for y in 0..height {
+        for x in 0..width {
+            let (mut sum, mut sum_weight) = (0.0, 0.0);
+            for (j, gy) in GAUSSIAN.iter().enumerate() {
+                for (i, gx) in GAUSSIAN.iter().enumerate() {
+                    let (qx, qy) = ((x + i).wrapping_sub(1), (y + j).wrapping_sub(1));
+                    if qx < width && qy < height {
+                        sum += gx * gy * values[qy * width + qx];
+                        sum_weight += gx * gy;
+                    }
+                }
+            }
+            out.push(sum / sum_weight);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        film::Pixel,
+        sampler::{hash, hashed_1d},
+    };
+
+    #[test]
+    fn removes_noise_and_keeps_edges() {
+        // The left half of the image is dark red and the right half light gray, with a
+        // different normal, rendered with noisy samples.
+        const SIZE: u32 = 32;
+        let truth = |x: u32| {
+            if x < SIZE / 2 {
+                Color::new(0.2, 0.05, 0.05)
+            } else {
+                Color::new(0.8, 0.8, 0.8)
+            }
+        };
+        let mut film = Film::new(SIZE, SIZE).with_aovs(&[Aov::Albedo, Aov::Normal]);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let (albedo, normal) = if x < SIZE / 2 {
+                    (Color::new(0.5, 0.125, 0.125), Color::new(1.0, 0.0, 0.0))
+                } else {
+                    (Color::new(0.5, 0.5, 0.5), Color::new(0.0, 0.0, 1.0))
+                };
+                // Samples are uniform between half and twice the average, times 0.8.
+                let sample = |i| {
+                    let u = hashed_1d(hash((y * SIZE + x) as u64, i));
+                    truth(x) * (2.0 * u + 0.5 * (1.0 - u)) / 1.25
+                };
+                let mut px = Pixel::default();
+                px.add(sample(0));
+                film.merge_pixel(x, y, &px, &[albedo, normal]);
+                for i in 1..8 {
+                    film.add_sample(x, y, sample(i));
+                }
+            }
+        }
+
+        let error = |film: &Film| {
+            let mut sum = 0.0;
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    sum += dot_self(film.get_pixel(x, y) - truth(x));
+                }
+            }
+            (sum / (SIZE * SIZE) as f64).sqrt()
+        };
+        let denoised = Denoiser::new().denoise(&film);
+        let (before, after) = (error(&film), error(&denoised));
+        assert!(after < 0.25 * before, "{} {}", before, after);
+
+        // Pixels next to the edge keep their side's color.
+        for y in 0..SIZE {
+            for x in [SIZE / 2 - 1, SIZE / 2] {
+                let d = denoised.get_pixel(x, y) - truth(x);
+                assert!(dot_self(d).sqrt() < 0.1, "{} {}", x, y);
+            }
+        }
+    }
+}
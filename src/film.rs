@@ -0,0 +1,310 @@
+use crate::{aov::Aov, color::Color, filter::Filter, tonemap::ToneMapping};
+
+/// Film accumulates linear radiance per pixel, rows are stored top to bottom.
+/// Besides samples of the pixels themselves it accepts splats anywhere on the image plane,
+/// as contributed by light paths reaching the camera.
+#[derive(Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    pixels: Vec<Pixel>,
+    filter: Filter,
+    // splats are summed filtered contributions, scaled by splat_scale when read.
+    splats: Vec<Color>,
+    splat_scale: f64,
+    aovs: Vec<Aov>,
+    // aov_values holds the values of all AOVs of a pixel next to each other.
+    aov_values: Vec<Color>,
+}
+
+/// Pixel keeps the running mean and sum of squared deviations of its samples (Welford).
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Pixel {
+    pub(crate) mean: Color,
+    pub(crate) m2: Color,
+    pub(crate) samples: u32,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Pixel::default(); (width * height) as usize],
+            filter: Filter::default(),
+            splats: vec![Color::default(); (width * height) as usize],
+            splat_scale: 1.0,
+            aovs: Vec::new(),
+            aov_values: Vec::new(),
+        }
+    }
+
+    pub(crate) fn from_pixels(
+        width: u32,
+        height: u32,
+        pixels: Vec<Pixel>,
+        splats: Vec<Color>,
+        aovs: Vec<Aov>,
+        aov_values: Vec<Color>,
+    ) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        assert_eq!(splats.len(), (width * height) as usize);
+        assert_eq!(aov_values.len(), aovs.len() * pixels.len());
+        Self {
+            pixels,
+            splats,
+            aovs,
+            aov_values,
+            ..Self::new(width, height)
+        }
+    }
+
+    /// with_filter sets the reconstruction filter splats are spread with.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// set_splat_scale sets the factor splats are multiplied with, usually one over the
+    /// number of paths that were traced to make them.
+    pub fn set_splat_scale(&mut self, scale: f64) {
+        self.splat_scale = scale;
+    }
+
+    /// with_aovs makes the film keep the AOVs, which the renderer then computes.
+    pub fn with_aovs(mut self, aovs: &[Aov]) -> Self {
+        self.aovs = aovs.to_vec();
+        self.aov_values = vec![Color::default(); aovs.len() * self.pixels.len()];
+        self
+    }
+
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    /// aov returns the values of the AOV for every pixel, if the film keeps it.
+    pub fn aov(&self, aov: Aov) -> Option<Vec<Color>> {
+        let k = self.aovs.iter().position(|&a| a == aov)?;
+        let n = self.aovs.len();
+        Some(self.aov_values.iter().skip(k).step_by(n).copied().collect())
+    }
+
+    pub(crate) fn aov_values(&self) -> &[Color] {
+        &self.aov_values
+    }
+
+    /// replace_radiance sets the radiance of every pixel, e.g. to the output of a denoiser.
+    /// Splats are folded in, while sample counts and variances stay those of the samples.
+    pub(crate) fn replace_radiance(&mut self, radiance: Vec<Color>) {
+        assert_eq!(radiance.len(), self.pixels.len());
+        for (px, c) in self.pixels.iter_mut().zip(radiance) {
+            px.mean = c;
+        }
+        self.splats.iter_mut().for_each(|s| *s = Color::default());
+    }
+
+    pub(crate) fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    pub(crate) fn splats(&self) -> &[Color] {
+        &self.splats
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, c: Color) {
+        self.pixels[(y * self.width + x) as usize].add(c);
+    }
+
+    /// add_weighted_sample adds a sample whose offset was drawn from the reconstruction
+    /// filter, with the weight returned by `Filter::sample`.
+    pub fn add_weighted_sample(&mut self, x: u32, y: u32, c: Color, weight: f64) {
+        self.add_sample(x, y, c * weight);
+    }
+
+    /// add_splat spreads a contribution at a point of the image plane over the pixels within
+    /// the filter radius. Pixel (x, y) covers [x, x + 1) x [y, y + 1) with y going down.
+    pub fn add_splat(&mut self, (px, py): (f64, f64), c: Color) {
+        let radius = self.filter.radius();
+        // Pixels whose centers are within the radius of the point.
+        let range = |p: f64, n: u32| {
+            let lo = (p - 0.5 - radius).ceil().max(0.0) as u32;
+            let hi = ((p - 0.5 + radius).floor() + 1.0).clamp(0.0, n as f64) as u32;
+            lo..hi
+        };
+        for y in range(py, self.height) {
+            for x in range(px, self.width) {
+                let offset = (x as f64 + 0.5 - px, y as f64 + 0.5 - py);
+                let weight = self.filter.evaluate(offset);
+                if weight != 0.0 {
+                    self.splats[(y * self.width + x) as usize] += c * weight;
+                }
+            }
+        }
+    }
+
+    /// merge_pixel adds samples accumulated separately, e.g. by a render thread, together
+    /// with the values of the AOVs over the same samples.
+    pub(crate) fn merge_pixel(&mut self, x: u32, y: u32, other: &Pixel, aov_values: &[Color]) {
+        let i = (y * self.width + x) as usize;
+        let n = self.aovs.len();
+        let samples = self.pixels[i].samples;
+        if other.samples > 0 {
+            let t = other.samples as f64 / (samples + other.samples) as f64;
+            for ((aov, value), &other) in self
+                .aovs
+                .iter()
+                .zip(&mut self.aov_values[i * n..(i + 1) * n])
+                .zip(aov_values)
+            {
+                if !aov.is_id() {
+                    *value += (other - *value) * t;
+                } else if samples == 0 {
+                    *value = other;
+                }
+            }
+        }
+        self.pixels[i].merge(other);
+    }
+
+    /// get_pixel returns the mean radiance of the pixel including splats.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        self.value((y * self.width + x) as usize)
+    }
+
+    fn value(&self, i: usize) -> Color {
+        self.pixels[i].mean + self.splats[i] * self.splat_scale
+    }
+
+    /// variance returns the sample variance of the pixel per channel.
+    pub fn variance(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize].variance()
+    }
+
+    /// relative_error is the half width of the 95% confidence interval of the pixel luminance
+    /// relative to the luminance itself. Pixels darker than MIN_LUMINANCE use it instead.
+    pub fn relative_error(&self, x: u32, y: u32) -> f64 {
+        const MIN_LUMINANCE: f64 = 0.01;
+
+        let px = &self.pixels[(y * self.width + x) as usize];
+        if px.samples < 2 {
+            return f64::INFINITY;
+        }
+        // The luminance of the standard errors bounds the standard error of the luminance.
+        let std_error = (px.variance() / px.samples as f64).map(f64::sqrt);
+        1.96 * std_error.luminance() / px.mean.luminance().max(MIN_LUMINANCE)
+    }
+
+    pub fn samples(&self, x: u32, y: u32) -> u32 {
+        self.pixels[(y * self.width + x) as usize].samples
+    }
+
+    /// sample_counts returns the number of samples of every pixel.
+    pub fn sample_counts(&self) -> Vec<u32> {
+        self.pixels.iter().map(|px| px.samples).collect()
+    }
+
+    /// radiance returns the mean linear radiance of every pixel.
+    pub fn radiance(&self) -> Vec<Color> {
+        (0..self.pixels.len()).map(|i| self.value(i)).collect()
+    }
+
+    /// tone_mapped returns display-referred pixels in [0, 1].
+    pub fn tone_mapped(&self, tone_mapping: &ToneMapping) -> Vec<Color> {
+        (0..self.pixels.len())
+            .map(|i| tone_mapping.apply(self.value(i)))
+            .collect()
+    }
+}
+
+impl Pixel {
+    pub(crate) fn add(&mut self, c: Color) {
+        self.samples += 1;
+        let delta = c - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (c - self.mean);
+    }
+
+    /// merge combines the statistics of two disjoint sets of samples (Chan et al.).
+    pub(crate) fn merge(&mut self, other: &Pixel) {
+        if other.samples == 0 {
+            return;
+        }
+        let n = (self.samples + other.samples) as f64;
+        let delta = other.mean - self.mean;
+        let weight = self.samples as f64 * other.samples as f64 / n;
+        self.mean += delta * (other.samples as f64 / n);
+        self.m2 += other.m2 + delta * delta * weight;
+        self.samples += other.samples;
+    }
+
+    fn variance(&self) -> Color {
+        if self.samples < 2 {
+            Color::default()
+        } else {
+            self.m2 / (self.samples - 1) as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_variance() {
+        let samples: Vec<Color> = (0..10)
+            .map(|i| Color::new(i as f64, (i * i) as f64, 1.0))
+            .collect();
+        let mean = samples.iter().fold(Color::default(), |a, &b| a + b) / 10.0;
+        let variance = samples
+            .iter()
+            .fold(Color::default(), |a, &b| a + (b - mean) * (b - mean))
+            / 9.0;
+
+        let mut film = Film::new(1, 1);
+        let mut other = Pixel::default();
+        for (i, &c) in samples.iter().enumerate() {
+            if i < 4 {
+                film.add_sample(0, 0, c);
+            } else {
+                other.add(c);
+            }
+        }
+        film.merge_pixel(0, 0, &other, &[]);
+
+        assert_eq!(film.samples(0, 0), 10);
+        for (a, b) in [
+            (film.get_pixel(0, 0), mean),
+            (film.variance(0, 0), variance),
+        ] {
+            assert!((a - b).map(f64::abs).max_component() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn splats() {
+        use crate::filter::FilterKind;
+
+        // A box filter puts splats into the pixel they fall in.
+        let mut film = Film::new(4, 3);
+        film.add_splat((2.3, 1.7), Color::new(1.0, 2.0, 3.0));
+        film.set_splat_scale(0.5);
+        assert_eq!(film.get_pixel(2, 1), Color::new(0.5, 1.0, 1.5));
+        assert_eq!(film.get_pixel(1, 1), Color::default());
+
+        // Wider filters spread them, keeping the total away from the borders.
+        let mut film = Film::new(8, 8).with_filter(Filter::new(FilterKind::Gaussian, 1.5));
+        film.add_splat((4.2, 3.9), Color::new(1.0, 1.0, 1.0));
+        let total: f64 = film.radiance().iter().map(|c| c.r()).sum();
+        assert!((total - 1.0).abs() < 0.02, "{}", total);
+        assert!(film.get_pixel(3, 3).r() > 0.0 && film.get_pixel(5, 4).r() > 0.0);
+    }
+}
@@ -0,0 +1,244 @@
+use std::f64::consts::PI;
+
+use serde::Deserialize;
+
+/// FilterKind is a pixel reconstruction filter, all of them are separable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterKind {
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(Self::Box),
+            "tent" => Some(Self::Tent),
+            "gaussian" => Some(Self::Gaussian),
+            "mitchell" => Some(Self::Mitchell),
+            "lanczos" => Some(Self::Lanczos),
+            _ => None,
+        }
+    }
+
+    /// default_radius is the radius in pixels used unless one is given.
+    pub fn default_radius(self) -> f64 {
+        match self {
+            Self::Box => 0.5,
+            Self::Tent => 1.0,
+            Self::Gaussian => 1.5,
+            Self::Mitchell => 2.0,
+            Self::Lanczos => 3.0,
+        }
+    }
+
+    /// evaluate_1d is the unnormalized filter along one axis at offset `x`.
+    fn evaluate_1d(self, x: f64, radius: f64) -> f64 {
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+        match self {
+            Self::Box => 1.0,
+            Self::Tent => 1.0 - x / radius,
+            Self::Gaussian => {
+                // The Gaussian is shifted down to reach zero at the radius.
+                let sigma = radius / 3.0;
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                gaussian(x) - gaussian(radius)
+            }
+            Self::Mitchell => {
+                // Mitchell-Netravali with B = C = 1/3 scaled from [-2, 2] to the radius.
+                const B: f64 = 1.0 / 3.0;
+                const C: f64 = 1.0 / 3.0;
+                let t = 2.0 * x / radius;
+                let v = if t > 1.0 {
+                    (-B - 6.0 * C) * t.powi(3)
+                        + (6.0 * B + 30.0 * C) * t * t
+                        + (-12.0 * B - 48.0 * C) * t
+                        + (8.0 * B + 24.0 * C)
+                } else {
+                    (12.0 - 9.0 * B - 6.0 * C) * t.powi(3)
+                        + (-18.0 + 12.0 * B + 6.0 * C) * t * t
+                        + (6.0 - 2.0 * B)
+                };
+                v / 6.0
+            }
+            Self::Lanczos => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// FilterSample is an offset from the pixel center and the weight of a sample taken there.
+pub struct FilterSample {
+    pub offset: (f64, f64),
+    // weight is the filter value divided by the density of the offset, normalized to average 1.
+    // It is negative in negative lobes of the Mitchell and Lanczos filters.
+    pub weight: f64,
+}
+
+/// Filter reconstructs pixels from samples around them. Offsets of samples are drawn
+/// proportionally to the magnitude of the filter, so every sample only contributes to its
+/// own pixel and the filter shows in the sample weights.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    kind: FilterKind,
+    radius: f64,
+    // integral of the filter along one axis.
+    integral: f64,
+    // cdf of the tabulated filter magnitude along one axis, cdf[0] = 0 and the last is 1.
+    cdf: Vec<f64>,
+}
+
+// BINS is the number of intervals the filter magnitude is tabulated at for sampling.
+const BINS: usize = 64;
+
+impl Filter {
+    /// new tabulates the filter for sampling, `radius` must be positive and finite.
+    pub fn new(kind: FilterKind, radius: f64) -> Self {
+        assert!(
+            radius > 0.0 && radius.is_finite(),
+            "filter radius must be positive, got {}",
+            radius
+        );
+        let f = |x: f64| kind.evaluate_1d(x, radius);
+        let width = 2.0 * radius / BINS as f64;
+
+        // The table takes the largest magnitude found in every bin, the small floor
+        // keeps the density positive wherever the filter may be non zero.
+        let bins: Vec<f64> = (0..BINS)
+            .map(|i| {
+                let x0 = -radius + i as f64 * width;
+                (0..=4)
+                    .map(|k| f(x0 + k as f64 * width / 4.0).abs())
+                    .fold(0.0, f64::max)
+            })
+            .collect();
+        let floor = 1e-3 * bins.iter().copied().fold(0.0, f64::max);
+        let mut cdf = Vec::with_capacity(BINS + 1);
+        cdf.push(0.0);
+        for v in bins {
+            cdf.push(cdf.last().unwrap() + v.max(floor));
+        }
+        let total = *cdf.last().unwrap();
+        cdf.iter_mut().for_each(|c| *c /= total);
+
+        // Midpoint rule, fine enough for these smooth filters.
+        const STEPS: usize = 4096;
+        let step = 2.0 * radius / STEPS as f64;
+        let integral = (0..STEPS)
+            .map(|i| f(-radius + (i as f64 + 0.5) * step))
+            .sum::<f64>()
+            * step;
+
+        Self {
+            kind,
+            radius,
+            integral,
+            cdf,
+        }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// evaluate returns the filter normalized to integrate to 1 at an offset from the pixel center.
+    pub fn evaluate(&self, (x, y): (f64, f64)) -> f64 {
+        self.kind.evaluate_1d(x, self.radius) * self.kind.evaluate_1d(y, self.radius)
+            / (self.integral * self.integral)
+    }
+
+    /// sample maps a point of the unit square to an offset from the pixel center.
+    pub fn sample(&self, (u1, u2): (f64, f64)) -> FilterSample {
+        let (x, pdf_x) = self.sample_1d(u1);
+        let (y, pdf_y) = self.sample_1d(u2);
+        FilterSample {
+            offset: (x, y),
+            weight: self.evaluate((x, y)) / (pdf_x * pdf_y),
+        }
+    }
+
+    /// sample_1d returns an offset along one axis and its density.
+    fn sample_1d(&self, u: f64) -> (f64, f64) {
+        let bin = (self.cdf.partition_point(|&c| c <= u) - 1).min(BINS - 1);
+        let p = self.cdf[bin + 1] - self.cdf[bin];
+        let width = 2.0 * self.radius / BINS as f64;
+        let t = ((u - self.cdf[bin]) / p).clamp(0.0, 1.0);
+        let x = -self.radius + (bin as f64 + t) * width;
+        (x, p / width)
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(FilterKind::Box, FilterKind::Box.default_radius())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::{Sampler, SobolSampler};
+
+    #[test]
+    fn normalized() {
+        for kind in [
+            FilterKind::Box,
+            FilterKind::Tent,
+            FilterKind::Gaussian,
+            FilterKind::Mitchell,
+            FilterKind::Lanczos,
+        ] {
+            let filter = Filter::new(kind, kind.default_radius());
+
+            // Samples stay within the radius and their weights average to one.
+            let mut sampler = SobolSampler::new(1);
+            const N: u32 = 4096;
+            let mut sum = 0.0;
+            for index in 0..N {
+                sampler.start_pixel_sample(0, 0, index);
+                let sample = filter.sample(sampler.get_2d());
+                let (x, y) = sample.offset;
+                assert!(x.abs() <= filter.radius() && y.abs() <= filter.radius());
+                sum += sample.weight;
+            }
+            let mean = sum / N as f64;
+            assert!((mean - 1.0).abs() < 0.01, "{:?}: {}", kind, mean);
+
+            // The normalized filter integrates to one.
+            let step = filter.radius() / 100.0;
+            let mut integral = 0.0;
+            for i in -100..100 {
+                for j in -100..100 {
+                    let p = ((i as f64 + 0.5) * step, (j as f64 + 0.5) * step);
+                    integral += filter.evaluate(p) * step * step;
+                }
+            }
+            assert!((integral - 1.0).abs() < 1e-3, "{:?}: {}", kind, integral);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "filter radius must be positive")]
+    fn zero_radius() {
+        Filter::new(FilterKind::Gaussian, 0.0);
+    }
+}
@@ -2,29 +2,31 @@ use crate::{
     aabb::AABB,
     material::Material,
     ray::Ray,
-    vec3::{Point, Vec3},
+    vec3::{Mat3, Point, Vec3},
 };
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Clone)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub p: Point,
     pub normal: Vec3,
     pub t: f64,
     pub u: f64,
     pub v: f64,
-    pub material: Rc<dyn Material>,
+    pub material: &'a dyn Material,
     pub front_face: bool,
+    // object_id is set by the closest enclosing `ObjectId`, 0 means none.
+    pub object_id: u32,
 }
 
-impl HitRecord {
+impl<'a> HitRecord<'a> {
     pub fn new(
         t: f64,
         p: Point,
         r: &Ray,
         u: f64,
         v: f64,
-        mat: Rc<dyn Material>,
+        mat: &'a dyn Material,
         outward_normal: Vec3,
     ) -> Self {
         let front_face = r.dir.dot(outward_normal) < 0.0;
@@ -36,6 +38,7 @@ impl HitRecord {
             v,
             material: mat,
             front_face,
+            object_id: 0,
             normal: if front_face {
                 outward_normal
             } else {
@@ -55,21 +58,21 @@ impl HitRecord {
 }
 
 // Hittable represents class of objects which can be intersected by a ray.
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     // hit must return true if r intersects an object at point t
     // such that t_min < t < t_max.
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
     // bounding_box return AABB containing the object.
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB>;
 }
 
 pub struct Translate {
-    pub object: Rc<dyn Hittable>,
+    pub object: Arc<dyn Hittable>,
     pub offset: Vec3,
 }
 
 impl Translate {
-    pub fn new(object: Rc<dyn Hittable>, offset: Vec3) -> Self {
+    pub fn new(object: Arc<dyn Hittable>, offset: Vec3) -> Self {
         Self { object, offset }
     }
 }
@@ -81,8 +84,8 @@ impl Hittable for Translate {
             .map(|obj| AABB::new(obj.min + self.offset, obj.max + self.offset))
     }
 
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let moved = Ray::new(r.orig - self.offset, r.dir, r.time);
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let moved = Ray::new(r.orig - self.offset, r.dir, r.time).with_sample(r.sample);
         self.object.hit(&moved, t_min, t_max).map(|mut rec| {
             rec.p += self.offset;
             rec.set_face_normal(&moved, rec.normal);
@@ -91,39 +94,58 @@ impl Hittable for Translate {
     }
 }
 
+/// ObjectId tags hits of an object with an id, e.g. for object id AOVs.
+pub struct ObjectId {
+    pub object: Arc<dyn Hittable>,
+    pub id: u32,
+}
+
+impl ObjectId {
+    pub fn new(object: Arc<dyn Hittable>, id: u32) -> Self {
+        Self { object, id }
+    }
+}
+
+impl Hittable for ObjectId {
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.object.bounding_box(time0, time1)
+    }
+
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.object.hit(r, t_min, t_max).map(|mut rec| {
+            rec.object_id = self.id;
+            rec
+        })
+    }
+}
+
 pub struct RotateY {
-    pub object: Rc<dyn Hittable>,
-    pub sin_theta: f64,
-    pub cos_theta: f64,
+    pub object: Arc<dyn Hittable>,
+    // rotation maps the object to world space.
+    pub rotation: Mat3,
     pub bbox: Option<AABB>,
 }
 
 impl RotateY {
-    pub fn new(object: Rc<dyn Hittable>, angle: f64) -> Self {
-        let r = angle.to_radians();
-        let sin_theta = r.sin();
-        let cos_theta = r.cos();
+    pub fn new(object: Arc<dyn Hittable>, angle: f64) -> Self {
+        let rotation = Mat3::rotation(angle, Vec3::y(1.0));
         let bbox = object.bounding_box(0.0, 1.0);
         Self {
             object,
-            sin_theta,
-            cos_theta,
+            rotation,
             bbox: bbox.map(|inner| {
                 let mut min = Vec3::new_eq(f64::INFINITY);
                 let mut max = Vec3::new_eq(f64::NEG_INFINITY);
 
-                min.y = inner.min.y;
-                max.y = inner.max.y;
                 for x in [inner.min.x, inner.max.x] {
-                    for z in [inner.min.z, inner.max.z] {
-                        let new_x = cos_theta * x + sin_theta * z;
-                        let new_z = -sin_theta * x + cos_theta * z;
-
-                        min.x = min.x.min(new_x);
-                        min.z = min.z.min(new_z);
-
-                        max.x = max.x.max(new_x);
-                        max.z = max.z.max(new_z);
+                    for y in [inner.min.y, inner.max.y] {
+                        for z in [inner.min.z, inner.max.z] {
+                            let corner = rotation * Vec3::new(x, y, z);
+                            for i in 0..3 {
+                                min[i] = min[i].min(corner[i]);
+                                max[i] = max[i].max(corner[i]);
+                            }
+                        }
                     }
                 }
 
@@ -138,28 +160,14 @@ impl Hittable for RotateY {
         self.bbox.clone()
     }
 
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let ox = self.cos_theta * r.orig.x - self.sin_theta * r.orig.z;
-        let oz = self.sin_theta * r.orig.x + self.cos_theta * r.orig.z;
-
-        let dx = self.cos_theta * r.dir.x - self.sin_theta * r.dir.z;
-        let dz = self.sin_theta * r.dir.x + self.cos_theta * r.dir.z;
-
-        let rotated = Ray::new(
-            Vec3::new(ox, r.orig.y, oz),
-            Vec3::new(dx, r.dir.y, dz),
-            r.time,
-        );
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // Rotations are orthogonal, the transpose rotates back into object space.
+        let inverse = self.rotation.transpose();
+        let rotated = Ray::new(inverse * r.orig, inverse * r.dir, r.time).with_sample(r.sample);
 
         self.object.hit(&rotated, t_min, t_max).map(|mut rec| {
-            let px = self.cos_theta * rec.p.x + self.sin_theta * rec.p.z;
-            let pz = -self.sin_theta * rec.p.x + self.cos_theta * rec.p.z;
-            let nx = self.cos_theta * rec.normal.x + self.sin_theta * rec.normal.z;
-            let nz = -self.sin_theta * rec.normal.x + self.cos_theta * rec.normal.z;
-
-            rec.p.x = px;
-            rec.p.z = pz;
-            rec.set_face_normal(&rotated, Vec3::new(nx, rec.normal.y, nz));
+            rec.p = self.rotation * rec.p;
+            rec.set_face_normal(&rotated, self.rotation * rec.normal);
 
             rec
         })
@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
     aabb::AABB,
@@ -7,11 +7,11 @@ use crate::{
 
 #[derive(Default)]
 pub struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
 }
 
 impl HittableList {
-    pub fn add(&mut self, obj: Rc<dyn Hittable>) {
+    pub fn add(&mut self, obj: Arc<dyn Hittable>) {
         self.objects.push(obj)
     }
 
@@ -19,15 +19,15 @@ impl HittableList {
         self.objects.clear()
     }
 
-    pub fn as_slice_mut(&mut self) -> &mut [Rc<dyn Hittable>] {
+    pub fn as_slice_mut(&mut self) -> &mut [Arc<dyn Hittable>] {
         &mut self.objects
     }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut closest_hit = t_max;
-        let mut rec: Option<HitRecord> = None;
+        let mut rec: Option<HitRecord<'_>> = None;
 
         for obj in self.objects.iter() {
             if let Some(temp_rec) = obj.hit(r, t_min, closest_hit) {
@@ -39,6 +39,9 @@ impl Hittable for HittableList {
         rec
     }
 
+    // bounding_box is None if any object is unbounded: a box around the others would let
+    // an enclosing BVH cull the list where only the unbounded object is hit. BVH builders
+    // keep such lists aside with the other unbounded objects.
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
         if self.objects.is_empty() {
             return None;
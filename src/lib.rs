@@ -1,30 +1,39 @@
-use rand::SeedableRng;
-
 pub mod aabb;
 pub mod aarect;
+pub mod aov;
 pub mod box3d;
 pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
 pub mod color;
 pub mod constant_medium;
+pub mod denoise;
+pub mod film;
+pub mod filter;
 pub mod hittable;
 pub mod hittable_list;
+pub mod light;
 pub mod material;
 pub mod moving_sphere;
+pub mod obj;
+pub mod output;
+pub mod pdf;
 pub mod perlin;
 pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene;
 pub mod sphere;
 pub mod texture;
+pub mod tonemap;
+pub mod transform;
+pub mod triangle;
 pub mod vec3;
 
 /// Rng is a concrete type for a random number generator used across the crate.
 /// It is here to easily switch between generators.
 pub type Rng = rand_xoshiro::Xoshiro256PlusPlus;
 
-pub fn thread_rng() -> Rng {
-    rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(42)
-}
-
 #[cfg(test)]
 mod tests {
     #[test]
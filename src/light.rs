@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    sampler::Sampler,
+    vec3::{Point, Vec3},
+};
+
+/// LightSample is a point on a light as seen from some origin.
+pub struct LightSample {
+    pub p: Point,
+    // wi is the unit direction from the origin to `p`.
+    pub wi: Vec3,
+    // pdf is measured with respect to the solid angle at the origin.
+    pub pdf: f64,
+    pub emitted: Color,
+}
+
+/// Light is an emitting object which can be sampled directly.
+pub trait Light: Hittable {
+    /// sample picks a point on the light, `None` means that nothing is visible from `origin`.
+    fn sample(&self, sampler: &mut dyn Sampler, origin: Point) -> Option<LightSample>;
+
+    /// pdf_value is the density of `sample` producing direction `dir` from `origin`.
+    fn pdf_value(&self, origin: Point, dir: Vec3) -> f64;
+
+    fn material(&self) -> &dyn Material;
+}
+
+/// area_to_solid_angle converts an area density at `p` with normal `n` into a solid angle one.
+pub(crate) fn area_to_solid_angle(
+    pdf: f64,
+    origin: Point,
+    p: Point,
+    n: Vec3,
+) -> Option<(Vec3, f64)> {
+    let d = p - origin;
+    let dist_squared = d.length_squared();
+    let wi = d / dist_squared.sqrt();
+    let cosine = wi.dot(n).abs();
+    // Degenerate shapes have NaN normals.
+    if cosine.is_nan() || cosine < 1e-8 || dist_squared == 0.0 {
+        return None;
+    }
+    Some((wi, pdf * dist_squared / cosine))
+}
+
+/// LightList picks one of its lights uniformly.
+#[derive(Default, Clone)]
+pub struct LightList {
+    lights: Vec<Arc<dyn Light>>,
+}
+
+impl LightList {
+    pub fn add(&mut self, light: Arc<dyn Light>) {
+        self.lights.push(light)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// sample picks a light uniformly and samples it.
+    pub fn sample(&self, sampler: &mut dyn Sampler, origin: Point) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let n = self.lights.len();
+        let light = &self.lights[((sampler.get_1d() * n as f64) as usize).min(n - 1)];
+        let mut sample = light.sample(sampler, origin)?;
+        sample.pdf /= self.lights.len() as f64;
+        Some(sample)
+    }
+
+    pub fn pdf_value(&self, origin: Point, dir: Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf_value(origin, dir))
+            .sum();
+        sum / self.lights.len() as f64
+    }
+
+    /// pdf_hit is the density of sampling the first light hit by `r`, measured with respect
+    /// to the solid angle at its origin. Unlike pdf_value, lights behind that one don't count.
+    pub fn pdf_hit(&self, r: &Ray) -> f64 {
+        let mut closest = f64::INFINITY;
+        let mut hit = None;
+        for light in self.lights.iter() {
+            if let Some(rec) = light.hit(r, 0.001, closest) {
+                closest = rec.t;
+                hit = Some(light);
+            }
+        }
+        hit.map_or(0.0, |light| {
+            light.pdf_value(r.orig, r.dir.normalize()) / self.lights.len() as f64
+        })
+    }
+
+    /// samples reports whether hits on `material` are accounted for by sampling lights.
+    pub fn samples(&self, material: &dyn Material) -> bool {
+        self.lights
+            .iter()
+            .any(|light| std::ptr::addr_eq(light.material(), material))
+    }
+}
+
+impl Hittable for LightList {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut rec = None;
+        for light in self.lights.iter() {
+            if let Some(hit) = light.hit(r, t_min, closest) {
+                closest = hit.t;
+                rec = Some(hit);
+            }
+        }
+        rec
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        let mut bbox: Option<AABB> = None;
+        for light in self.lights.iter() {
+            let b = light.bounding_box(time0, time1)?;
+            bbox = Some(match bbox {
+                Some(acc) => acc.union(&b),
+                None => b,
+            });
+        }
+        bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aarect::YZRect, material::DiffuseLight, sampler::IndependentSampler, sphere::Sphere,
+        triangle::Triangle,
+    };
+
+    #[test]
+    fn sample_pdf() {
+        let material: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)));
+        let lights: Vec<Arc<dyn Light>> = vec![
+            Arc::new(Sphere::new(
+                Point::new(0.0, 0.0, -3.0),
+                1.0,
+                material.clone(),
+            )),
+            Arc::new(Sphere::new(
+                Point::new(0.0, 0.0, 0.5),
+                1.0,
+                material.clone(),
+            )),
+            Arc::new(YZRect::new(-1.0, 1.0, -2.0, 1.0, 2.0, material.clone())),
+            Arc::new(Triangle::new(
+                Point::new(-1.0, 2.0, -1.0),
+                Point::new(1.0, 2.0, -1.0),
+                Point::new(0.0, 2.0, 1.0),
+                material,
+            )),
+        ];
+
+        let mut sampler = IndependentSampler::new(42);
+        for light in lights {
+            for _ in 0..100 {
+                let sample = light.sample(&mut sampler, Point::default()).unwrap();
+                let pdf = light.pdf_value(Point::default(), sample.wi);
+                assert!(
+                    (sample.pdf - pdf).abs() < 1e-6 * pdf,
+                    "{} != {}",
+                    sample.pdf,
+                    pdf
+                );
+                assert!((sample.p.normalize() - sample.wi).near_zero());
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_hit() {
+        let material: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)));
+        let front = Arc::new(YZRect::new(-1.0, 1.0, -1.0, 1.0, 2.0, material.clone()));
+        let back = Arc::new(YZRect::new(-1.0, 1.0, -1.0, 1.0, 3.0, material));
+        let mut lights = LightList::default();
+        lights.add(front.clone());
+        lights.add(back);
+
+        let r = Ray::new(Point::default(), Vec3::x(1.0), 0.0);
+        let pdf = front.pdf_value(r.orig, r.dir);
+        assert_eq!(lights.pdf_hit(&r), pdf / 2.0);
+        assert!(lights.pdf_value(r.orig, r.dir) > pdf / 2.0);
+        assert_eq!(lights.pdf_hit(&Ray::new(r.orig, -r.dir, 0.0)), 0.0);
+    }
+}
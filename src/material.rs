@@ -1,48 +1,111 @@
-use std::rc::Rc;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 use crate::{
     color::Color,
     hittable::HitRecord,
+    pdf::{CosinePdf, Pdf, UniformPdf},
     ray::Ray,
+    sampler::Sampler,
     texture::{SolidColor, Texture},
-    vec3::{random_in_unit_sphere, Point},
+    vec3::{sample_unit_sphere, Point, Vec3},
 };
 
-use rand::Rng;
+/// ScatterRecord is a direction sampled by a material.
+pub struct ScatterRecord {
+    pub ray: Ray,
+    // attenuation is `eval` divided by `pdf` for the sampled direction.
+    pub attenuation: Color,
+    // pdf is the density of the sampled direction, `None` for specular scattering.
+    pub pdf: Option<f64>,
+}
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn emitted(&self, _u: f64, _v: f64, _p: &Point) -> Color {
         Color::default()
     }
-    fn scatter(&self, rng: &mut crate::Rng, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)>;
+
+    /// albedo is the fraction of light the surface reflects, as written to albedo AOVs.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    /// sample picks a scattered direction, `None` means that the ray is absorbed.
+    fn sample(
+        &self,
+        _sampler: &mut dyn Sampler,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+    ) -> Option<ScatterRecord> {
+        None
+    }
+
+    /// pdf is the density of `sample` producing the unit direction `wi`.
+    /// It is zero for specular materials which scatter to a single direction.
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _wi: Vec3) -> f64 {
+        0.0
+    }
+
+    /// eval returns the BSDF times the cosine term for the unit direction `wi`.
+    /// It is zero for specular materials.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _wi: Vec3) -> Color {
+        Color::default()
+    }
+
+    fn scatter(
+        &self,
+        sampler: &mut dyn Sampler,
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> Option<(Ray, Color)> {
+        self.sample(sampler, r_in, rec)
+            .map(|s| (s.ray, s.attenuation))
+    }
 }
 
 pub struct Lambertian {
-    albedo: Rc<dyn Texture>,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
         Self {
-            albedo: Rc::new(SolidColor::new(albedo)),
+            albedo: Arc::new(SolidColor::new(albedo)),
         }
     }
 
-    pub fn new_with_texture(albedo: Rc<dyn Texture>) -> Self {
+    pub fn new_with_texture(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, rng: &mut crate::Rng, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = rec.normal + random_in_unit_sphere(rng);
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
-        Some((
-            Ray::new(rec.p, scatter_direction, r_in.time),
-            self.albedo.value(rec.u, rec.v, &rec.p),
-        ))
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn sample(
+        &self,
+        sampler: &mut dyn Sampler,
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> Option<ScatterRecord> {
+        let pdf = CosinePdf::new(rec.normal);
+        let direction = pdf.generate(sampler)?;
+        Some(ScatterRecord {
+            ray: Ray::new(rec.p, direction, r_in.time),
+            attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+            pdf: Some(pdf.value(direction)),
+        })
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, wi: Vec3) -> f64 {
+        CosinePdf::new(rec.normal).value(wi)
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, wi: Vec3) -> Color {
+        let cosine = rec.normal.dot(wi).max(0.0);
+        self.albedo.value(rec.u, rec.v, &rec.p) * (cosine / PI)
     }
 }
 
@@ -58,18 +121,66 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, rng: &mut crate::Rng, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn sample(
+        &self,
+        sampler: &mut dyn Sampler,
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> Option<ScatterRecord> {
         let reflected = r_in.dir.normalize().reflect(rec.normal);
-        let scattered = Ray::new(
-            rec.p,
-            reflected + self.fuzz * random_in_unit_sphere(rng),
-            r_in.time,
-        );
-        if scattered.dir.dot(rec.normal) > 0.0 {
-            Some((scattered, self.albedo))
-        } else {
-            None
+        if self.fuzz <= 0.0 {
+            return Some(ScatterRecord {
+                ray: Ray::new(rec.p, reflected, r_in.time),
+                attenuation: self.albedo,
+                pdf: None,
+            });
+        }
+
+        // A uniform point in the unit ball.
+        let offset = sample_unit_sphere(sampler.get_2d()) * sampler.get_1d().cbrt();
+        let direction = reflected + self.fuzz * offset;
+        if direction.dot(rec.normal) <= 0.0 || direction.near_zero() {
+            return None;
         }
+        let direction = direction.normalize();
+        Some(ScatterRecord {
+            ray: Ray::new(rec.p, direction, r_in.time),
+            attenuation: self.albedo,
+            pdf: Some(self.pdf(r_in, rec, direction)),
+        })
+    }
+
+    /// pdf of a fuzzy reflection is the density of directions through a point
+    /// uniformly distributed in the ball of radius `fuzz` around the mirror direction:
+    /// `(t2^3 - t1^3) / (4 pi fuzz^3)`, where the ray enters the ball at t1 and leaves at t2.
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: Vec3) -> f64 {
+        if self.fuzz <= 0.0 {
+            return 0.0;
+        }
+        let reflected = r_in.dir.normalize().reflect(rec.normal);
+        let b = wi.dot(reflected);
+        let d = b * b - (1.0 - self.fuzz * self.fuzz);
+        if d < 0.0 {
+            return 0.0;
+        }
+        let t1 = (b - d.sqrt()).max(0.0);
+        let t2 = b + d.sqrt();
+        if t2 <= 0.0 {
+            return 0.0;
+        }
+        (t2.powi(3) - t1.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: Vec3) -> Color {
+        // Directions below the surface are absorbed.
+        if wi.dot(rec.normal) <= 0.0 {
+            return Color::default();
+        }
+        self.albedo * self.pdf(r_in, rec, wi)
     }
 }
 
@@ -93,7 +204,16 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, rng: &mut crate::Rng, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn sample(
+        &self,
+        sampler: &mut dyn Sampler,
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> Option<ScatterRecord> {
         let refraction_ratio = if rec.front_face {
             self.ri.recip()
         } else {
@@ -103,71 +223,124 @@ impl Material for Dielectric {
         let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || Self::reflectance(cos_theta, refraction_ratio) > rng.gen::<f64>()
-        {
+        // The sample is drawn even for total internal reflection to keep dimensions aligned.
+        let u = sampler.get_1d();
+        let direction = if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > u {
             unit_direction.reflect(rec.normal)
         } else {
             unit_direction.refract(rec.normal, refraction_ratio)
         };
-        Some((
-            Ray::new(rec.p, direction, r_in.time),
-            Color::new(1.0, 1.0, 1.0),
-        ))
+        Some(ScatterRecord {
+            ray: Ray::new(rec.p, direction, r_in.time),
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            pdf: None,
+        })
     }
 }
 
 pub struct DiffuseLight {
-    pub emit: Rc<dyn Texture>,
+    pub emit: Arc<dyn Texture>,
 }
 
 impl DiffuseLight {
     pub fn new(c: Color) -> Self {
         Self {
-            emit: Rc::new(SolidColor::new(c)),
+            emit: Arc::new(SolidColor::new(c)),
         }
     }
 
-    pub fn new_with_texture(texture: Rc<dyn Texture>) -> Self {
+    pub fn new_with_texture(texture: Arc<dyn Texture>) -> Self {
         Self { emit: texture }
     }
 }
 
 impl Material for DiffuseLight {
-    fn scatter(
-        &self,
-        _rng: &mut crate::Rng,
-        _r_in: &Ray,
-        _rec: &HitRecord,
-    ) -> Option<(Ray, Color)> {
-        None
+    // Lights are seen as their emitted color clamped to [0, 1].
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.emit.value(rec.u, rec.v, &rec.p).clamp(0.0, 1.0)
     }
+
     fn emitted(&self, u: f64, v: f64, p: &Point) -> Color {
         self.emit.value(u, v, p)
     }
 }
 
 pub struct Isotropic {
-    albedo: Rc<dyn Texture>,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Isotropic {
     pub fn new(c: Color) -> Self {
         Self {
-            albedo: Rc::new(SolidColor::new(c)),
+            albedo: Arc::new(SolidColor::new(c)),
         }
     }
 
-    pub fn new_with_texture(albedo: Rc<dyn Texture>) -> Self {
+    pub fn new_with_texture(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, rng: &mut crate::Rng, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        Some((
-            Ray::new(rec.p, random_in_unit_sphere(rng), r_in.time),
-            self.albedo.value(rec.u, rec.v, &rec.p),
-        ))
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn sample(
+        &self,
+        sampler: &mut dyn Sampler,
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> Option<ScatterRecord> {
+        let direction = UniformPdf.generate(sampler)?;
+        Some(ScatterRecord {
+            ray: Ray::new(rec.p, direction, r_in.time),
+            attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+            pdf: Some(UniformPdf.value(direction)),
+        })
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, wi: Vec3) -> f64 {
+        UniformPdf.value(wi)
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, wi: Vec3) -> Color {
+        // The phase function is uniform over the sphere.
+        self.albedo.value(rec.u, rec.v, &rec.p) * UniformPdf.value(wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    #[test]
+    fn metal_pdf() {
+        let mut sampler = IndependentSampler::new(42);
+        let r_in = Ray::new(Point::new(0.0, 1.0, -1.0), Vec3::new(0.0, -1.0, 1.0), 0.0);
+
+        for fuzz in [0.3, 1.5] {
+            let metal = Metal::new(Color::new(0.8, 0.8, 0.8), fuzz);
+            let rec = HitRecord::new(1.0, Point::default(), &r_in, 0.0, 0.0, &metal, Vec3::y(1.0));
+
+            // The density over the whole sphere including absorbed directions integrates to 1.
+            const N: usize = 200_000;
+            let sum: f64 = (0..N)
+                .map(|_| metal.pdf(&r_in, &rec, sample_unit_sphere(sampler.get_2d())))
+                .sum();
+            let integral = sum * 4.0 * PI / N as f64;
+            assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+
+            // Samples below the surface are absorbed.
+            let s = loop {
+                if let Some(s) = metal.sample(&mut sampler, &r_in, &rec) {
+                    break s;
+                }
+            };
+            let wi = s.ray.dir;
+            let weight = metal.eval(&r_in, &rec, wi) / s.pdf.unwrap();
+            assert!((weight.g() - s.attenuation.g()).abs() < 1e-9);
+        }
     }
 }
@@ -1,4 +1,4 @@
-use std::{ops::Range, rc::Rc};
+use std::{ops::Range, sync::Arc};
 
 use crate::{
     aabb::AABB,
@@ -14,7 +14,7 @@ pub struct MovingSphere {
     pub center1: Point,
     pub time: Range<f64>,
     pub radius: f64,
-    pub material: Rc<dyn Material>,
+    pub material: Arc<dyn Material>,
 }
 
 impl MovingSphere {
@@ -23,7 +23,7 @@ impl MovingSphere {
         center1: Point,
         time: Range<f64>,
         radius: f64,
-        material: Rc<dyn Material>,
+        material: Arc<dyn Material>,
     ) -> Self {
         Self {
             center0,
@@ -42,7 +42,7 @@ impl MovingSphere {
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let center = self.center_at(r.time);
         let oc = r.orig - center;
         let a = r.dir.length_squared();
@@ -74,7 +74,7 @@ impl Hittable for MovingSphere {
             r,
             u,
             v,
-            self.material.clone(),
+            self.material.as_ref(),
             outward_normal,
         ))
     }
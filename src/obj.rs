@@ -0,0 +1,564 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use std::sync::Arc;
+
+use crate::{
+    color::Color,
+    hittable_list::HittableList,
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    texture::ImageTexture,
+    triangle::{Mesh, TriangleMesh},
+    vec3::{Point, Vec3},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, std::io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Texture(PathBuf),
+    Empty(PathBuf),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "can't read {}: {}", path.display(), err),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Self::Texture(path) => write!(f, "can't load texture {}", path.display()),
+            Self::Empty(path) => write!(f, "{} has no faces", path.display()),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// load reads a Wavefront OBJ file together with its MTL libraries.
+/// Faces are grouped into one triangle mesh per group and material,
+/// faces without a material use `default_material`.
+pub fn load(path: &Path, default_material: Arc<dyn Material>) -> Result<HittableList, ObjError> {
+    load_with_files(path, default_material, &mut Vec::new())
+}
+
+/// load_with_files is `load` which also appends every file it reads to `files`:
+/// the model, its MTL libraries and their textures.
+pub fn load_with_files(
+    path: &Path,
+    default_material: Arc<dyn Material>,
+    files: &mut Vec<PathBuf>,
+) -> Result<HittableList, ObjError> {
+    files.push(path.to_owned());
+    let text = read(path)?;
+    let meshes = parse(&text, path, default_material, files)?;
+    if meshes.is_empty() {
+        return Err(ObjError::Empty(path.to_owned()));
+    }
+
+    let mut list = HittableList::default();
+    for mesh in meshes {
+        list.add(Arc::new(mesh));
+    }
+    Ok(list)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|err| ObjError::Io(path.to_owned(), err))
+}
+
+/// MeshBuilder collects faces of the current group and material.
+struct MeshBuilder {
+    material: Arc<dyn Material>,
+    // vertices maps (position, uv, normal) index triples to mesh vertices.
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    positions: Vec<Point>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    indices: Vec<[u32; 3]>,
+    has_normals: bool,
+    has_uvs: bool,
+}
+
+impl MeshBuilder {
+    fn new(material: Arc<dyn Material>) -> Self {
+        Self {
+            material,
+            vertices: HashMap::new(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+            has_normals: false,
+            has_uvs: false,
+        }
+    }
+
+    fn vertex(&mut self, data: &ObjData, key: (usize, Option<usize>, Option<usize>)) -> u32 {
+        if let Some(&i) = self.vertices.get(&key) {
+            return i;
+        }
+
+        let (p, uv, n) = key;
+        self.positions.push(data.positions[p]);
+        // Vertices without a normal get a zero one, so the face normal is used instead.
+        self.normals
+            .push(n.map_or(Vec3::default(), |n| data.normals[n]));
+        self.uvs.push(uv.map_or((0.0, 0.0), |uv| data.uvs[uv]));
+        self.has_normals |= n.is_some();
+        self.has_uvs |= uv.is_some();
+
+        let i = (self.positions.len() - 1) as u32;
+        self.vertices.insert(key, i);
+        i
+    }
+
+    fn build(mut self) -> Option<Mesh> {
+        if self.indices.is_empty() {
+            return None;
+        }
+        if !self.has_normals {
+            self.normals.clear();
+        }
+        if !self.has_uvs {
+            self.uvs.clear();
+        }
+        Some(Mesh {
+            positions: self.positions,
+            normals: self.normals,
+            uvs: self.uvs,
+            indices: self.indices,
+            material: self.material,
+        })
+    }
+}
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<Point>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+}
+
+fn parse(
+    text: &str,
+    path: &Path,
+    default_material: Arc<dyn Material>,
+    files: &mut Vec<PathBuf>,
+) -> Result<Vec<TriangleMesh>, ObjError> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut data = ObjData::default();
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut current = MeshBuilder::new(default_material.clone());
+    let mut meshes = Vec::new();
+
+    let mut finish = |builder: MeshBuilder, line: usize| -> Result<(), ObjError> {
+        if let Some(mesh) = builder.build() {
+            let mesh = TriangleMesh::new(mesh).map_err(|message| ObjError::Parse {
+                path: path.to_owned(),
+                line,
+                message,
+            })?;
+            meshes.push(mesh);
+        }
+        Ok(())
+    };
+
+    for (line_no, line) in logical_lines(text) {
+        let err = |message: String| ObjError::Parse {
+            path: path.to_owned(),
+            line: line_no,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&mut tokens, 3).map_err(err)?;
+                data.positions.push(Point::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(&mut tokens, 3).map_err(err)?;
+                data.normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v, _] = parse_floats::<3>(&mut tokens, 1).map_err(err)?;
+                data.uvs.push((u, v));
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|t| parse_corner(t, &data))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+                if corners.len() < 3 {
+                    return Err(err(format!(
+                        "face needs at least 3 vertices, got {}",
+                        corners.len()
+                    )));
+                }
+
+                let indices: Vec<u32> = corners
+                    .into_iter()
+                    .map(|key| current.vertex(&data, key))
+                    .collect();
+                // Polygons are triangulated as a fan, which is correct for convex ones.
+                for i in 1..indices.len() - 1 {
+                    current
+                        .indices
+                        .push([indices[0], indices[i], indices[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                let material = current.material.clone();
+                finish(
+                    std::mem::replace(&mut current, MeshBuilder::new(material)),
+                    line_no,
+                )?;
+            }
+            "usemtl" => {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| err("usemtl needs a material name".into()))?;
+                let material = materials
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| err(format!("unknown material '{}'", name)))?;
+                finish(
+                    std::mem::replace(&mut current, MeshBuilder::new(material)),
+                    line_no,
+                )?;
+            }
+            "mtllib" => {
+                let names: Vec<&str> = tokens.collect();
+                if names.is_empty() {
+                    return Err(err("mtllib needs a file name".into()));
+                }
+                for name in names {
+                    let mtl_path = base_dir.join(name);
+                    files.push(mtl_path.clone());
+                    let text = read(&mtl_path)?;
+                    materials.extend(parse_mtl(&text, &mtl_path, files)?);
+                }
+            }
+            // Other statements, e.g. smoothing groups, lines, points and free-form
+            // geometry, are not rendered.
+            _ => {}
+        }
+    }
+    finish(current, text.lines().count())?;
+
+    Ok(meshes)
+}
+
+/// logical_lines joins lines continued by a trailing backslash, it returns them with the number
+/// of their first line.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut continued: Option<(usize, String)> = None;
+    for (n, line) in text.lines().enumerate() {
+        let (line_no, mut joined) = continued.take().unwrap_or((n + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                joined.push_str(line);
+                joined.push(' ');
+                continued = Some((line_no, joined));
+            }
+            None => {
+                joined.push_str(line);
+                lines.push((line_no, joined));
+            }
+        }
+    }
+    lines.extend(continued);
+    lines
+}
+
+fn parse_floats<const N: usize>(
+    tokens: &mut SplitWhitespace<'_>,
+    required: usize,
+) -> Result<[f64; N], String> {
+    let mut values = [0.0; N];
+    let mut count = 0;
+    for token in tokens {
+        if count == N {
+            // OBJ allows an optional w component which is ignored.
+            break;
+        }
+        values[count] = token
+            .parse()
+            .map_err(|_| format!("'{}' is not a number", token))?;
+        count += 1;
+    }
+    if count < required {
+        return Err(format!("expected {} numbers, got {}", required, count));
+    }
+    Ok(values)
+}
+
+/// parse_corner parses `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero-based indices.
+fn parse_corner(
+    token: &str,
+    data: &ObjData,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = token.split('/');
+    let p = parts.next().unwrap_or_default();
+    let uv = parts.next().filter(|s| !s.is_empty());
+    let n = parts.next().filter(|s| !s.is_empty());
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex '{}'", token));
+    }
+
+    let p = resolve_index(p, data.positions.len(), "vertex")?;
+    let uv = uv
+        .map(|uv| resolve_index(uv, data.uvs.len(), "texture coordinate"))
+        .transpose()?;
+    let n = n
+        .map(|n| resolve_index(n, data.normals.len(), "normal"))
+        .transpose()?;
+    Ok((p, uv, n))
+}
+
+/// resolve_index converts one-based and negative (relative to the end) indices.
+fn resolve_index(token: &str, len: usize, what: &str) -> Result<usize, String> {
+    let i: i64 = token
+        .parse()
+        .map_err(|_| format!("invalid {} index '{}'", what, token))?;
+    let resolved = if i > 0 { i - 1 } else { len as i64 + i };
+    if i == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!("{} index {} is out of bounds", what, i));
+    }
+    Ok(resolved as usize)
+}
+
+/// MtlMaterial holds the parameters of a single `newmtl` entry.
+struct MtlMaterial {
+    kd: Color,
+    ks: Color,
+    ke: Color,
+    ns: f64,
+    ni: f64,
+    dissolve: f64,
+    illum: u32,
+    map_kd: Option<PathBuf>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::default(),
+            ke: Color::default(),
+            ns: 0.0,
+            ni: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// build maps MTL parameters on the closest available material.
+    fn build(self, path: &Path, files: &mut Vec<PathBuf>) -> Result<Arc<dyn Material>, ObjError> {
+        if self.ke.luminance() > 0.0 {
+            return Ok(Arc::new(DiffuseLight::new(self.ke)));
+        }
+        if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            return Ok(Arc::new(Dielectric::new(self.ni)));
+        }
+        if matches!(self.illum, 3 | 5) {
+            // Convert Phong exponent to roughness.
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt().clamp(0.0, 1.0);
+            return Ok(Arc::new(Metal::new(self.ks, fuzz)));
+        }
+        match self.map_kd {
+            Some(map) => {
+                let map = path.parent().unwrap_or_else(|| Path::new("")).join(map);
+                files.push(map.clone());
+                let texture =
+                    ImageTexture::new(&map.to_string_lossy()).ok_or(ObjError::Texture(map))?;
+                Ok(Arc::new(Lambertian::new_with_texture(Arc::new(texture))))
+            }
+            None => Ok(Arc::new(Lambertian::new(self.kd))),
+        }
+    }
+}
+
+fn parse_mtl(
+    text: &str,
+    path: &Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (line_no, line) in logical_lines(text) {
+        let err = |message: String| ObjError::Parse {
+            path: path.to_owned(),
+            line: line_no,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| err("newmtl needs a material name".into()))?;
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl.build(path, files)?);
+            }
+            current = Some((name.to_owned(), MtlMaterial::default()));
+            continue;
+        }
+
+        let Some((_, mtl)) = current.as_mut() else {
+            return Err(err(format!("'{}' before newmtl", keyword)));
+        };
+        let color = |tokens: &mut SplitWhitespace<'_>| {
+            parse_floats::<3>(tokens, 3).map(|[r, g, b]| Color::new(r, g, b))
+        };
+        let float = |tokens: &mut SplitWhitespace<'_>| parse_floats::<1>(tokens, 1).map(|[x]| x);
+        match keyword {
+            "Kd" => mtl.kd = color(&mut tokens).map_err(err)?,
+            "Ks" => mtl.ks = color(&mut tokens).map_err(err)?,
+            "Ke" => mtl.ke = color(&mut tokens).map_err(err)?,
+            "Ns" => mtl.ns = float(&mut tokens).map_err(err)?,
+            "Ni" => mtl.ni = float(&mut tokens).map_err(err)?,
+            "d" => mtl.dissolve = float(&mut tokens).map_err(err)?,
+            "Tr" => mtl.dissolve = 1.0 - float(&mut tokens).map_err(err)?,
+            "illum" => {
+                let token = tokens.next().unwrap_or_default();
+                mtl.illum = token
+                    .parse()
+                    .map_err(|_| err(format!("invalid illumination model '{}'", token)))?;
+            }
+            "map_Kd" => {
+                // Options like `-s 1 1 1` precede the file name.
+                let file = tokens
+                    .last()
+                    .ok_or_else(|| err("map_Kd needs a file name".into()))?;
+                mtl.map_kd = Some(PathBuf::from(file));
+            }
+            // Other parameters and maps are not supported by the materials.
+            _ => {}
+        }
+    }
+    if let Some((name, mtl)) = current.take() {
+        materials.insert(name, mtl.build(path, files)?);
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::ray::Ray;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn faces() {
+        let obj = "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 1
+            vn 0 0 1
+            g quad
+            f 1/1/1 2//1 3/2/1 4
+            g triangle
+            v 0 0 -1
+            v 1 0 -1
+            v 0 1 -1
+            f -3 -2 -1
+        ";
+        let meshes = parse(obj, Path::new("test.obj"), material(), &mut Vec::new()).unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].mesh().indices.len(), 2);
+        assert_eq!(meshes[0].mesh().positions.len(), 4);
+        assert_eq!(meshes[1].mesh().indices, vec![[0, 1, 2]]);
+        assert!(meshes[1].mesh().normals.is_empty());
+
+        let r = Ray::new(Point::new(0.2, 0.3, 1.0), Vec3::z(-1.0), 0.0);
+        let rec = meshes[1].hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn errors() {
+        let check = |obj: &str, line: usize| match parse(
+            obj,
+            Path::new("test.obj"),
+            material(),
+            &mut Vec::new(),
+        ) {
+            Err(ObjError::Parse { line: l, .. }) => assert_eq!(l, line, "{}", obj),
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("no error for {}", obj),
+        };
+        check("v 0 0 0\nv 1 0\n", 2);
+        check("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", 4);
+        check("v 0 0 0\nv 1 0 0\nf 1 2\n", 3);
+        check("v 0 0 0\nv 1 x 0\n", 2);
+        check("\nusemtl missing\n", 2);
+        check("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 \\\n x\n", 4);
+
+        // Statements which aren't rendered are skipped, lines may be continued.
+        let obj = "mg 1\nvp 0.5\ncstype bezier\nv 0 0 0\nv 1 0 \\\n 0\nv 0 1 0\nf 1 2 3\n";
+        let meshes = parse(obj, Path::new("test.obj"), material(), &mut Vec::new()).unwrap();
+        assert_eq!(meshes[0].mesh().positions.len(), 3);
+    }
+
+    #[test]
+    fn mtl() {
+        let mtl = "
+            newmtl light
+            Ke 10 10 10
+            newmtl glass
+            Ni 1.33
+            d 0.5
+            newmtl chrome
+            map_refl sky.png
+            Ka 0.1 0.1 0.1
+            illum 3
+            Ks 0.9 0.9 0.9
+            Ns 500
+        ";
+        let materials = parse_mtl(mtl, Path::new("test.mtl"), &mut Vec::new()).unwrap();
+        assert_eq!(materials.len(), 3);
+
+        assert!(parse_mtl("Kd 1 1 1\n", Path::new("test.mtl"), &mut Vec::new()).is_err());
+    }
+}
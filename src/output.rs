@@ -0,0 +1,402 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use image::{codecs::hdr::HdrEncoder, ImageBuffer, ImageError, Rgb};
+
+use crate::{
+    aov::Aov,
+    color::Color,
+    film::Film,
+    sampler::{hash, hashed_1d},
+    tonemap::ToneMapping,
+};
+
+/// OutputFormat is a file format the film can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// 8-bit PNG, tone mapped.
+    Png,
+    /// 16-bit PNG, tone mapped.
+    Png16,
+    /// Radiance RGBE, linear.
+    Hdr,
+    /// OpenEXR with 32-bit float channels, linear. AOVs are stored as layers.
+    Exr,
+}
+
+impl OutputFormat {
+    /// from_path picks the format by the file extension, PNG files are 8-bit unless changed
+    /// with `with_bit_depth`.
+    pub fn from_path(path: &Path) -> Result<Self, OutputError> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "png" => Ok(Self::Png),
+            "hdr" => Ok(Self::Hdr),
+            "exr" => Ok(Self::Exr),
+            _ => Err(OutputError::UnsupportedFormat(ext)),
+        }
+    }
+
+    /// with_bit_depth picks the PNG variant with `bits` per channel, 8 or 16.
+    /// Other formats have a fixed depth and are returned as they are.
+    pub fn with_bit_depth(self, bits: u8) -> Self {
+        match self {
+            Self::Png | Self::Png16 if bits == 16 => Self::Png16,
+            Self::Png | Self::Png16 => Self::Png,
+            format => format,
+        }
+    }
+
+    /// is_hdr returns true if the format stores linear floating point data.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, Self::Hdr | Self::Exr)
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    UnsupportedFormat(String),
+    Io(std::io::Error),
+    Image(ImageError),
+    Exr(exr::error::Error),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(ext) if ext.is_empty() => {
+                write!(f, "output file has no extension, use .png, .hdr or .exr")
+            }
+            Self::UnsupportedFormat(ext) => {
+                write!(
+                    f,
+                    "unsupported output format '.{}', use .png, .hdr or .exr",
+                    ext
+                )
+            }
+            Self::Io(err) => write!(f, "can't write output: {}", err),
+            Self::Image(err) => write!(f, "can't encode output: {}", err),
+            Self::Exr(err) => write!(f, "can't encode output: {}", err),
+        }
+    }
+}
+
+impl Error for OutputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::UnsupportedFormat(_) => None,
+            Self::Io(err) => Some(err),
+            Self::Image(err) => Some(err),
+            Self::Exr(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for OutputError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ImageError> for OutputError {
+    fn from(err: ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
+impl From<exr::error::Error> for OutputError {
+    fn from(err: exr::error::Error) -> Self {
+        Self::Exr(err)
+    }
+}
+
+/// save writes the film to path, the format is chosen by the extension.
+/// Tone mapping is only applied to low dynamic range formats.
+pub fn save(
+    film: &Film,
+    path: impl AsRef<Path>,
+    tone_mapping: &ToneMapping,
+) -> Result<(), OutputError> {
+    let path = path.as_ref();
+    save_with_format(film, path, OutputFormat::from_path(path)?, tone_mapping)
+}
+
+pub fn save_with_format(
+    film: &Film,
+    path: impl AsRef<Path>,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+) -> Result<(), OutputError> {
+    let path = path.as_ref();
+    let (width, height) = (film.width(), film.height());
+    match format {
+        OutputFormat::Png => {
+            let data = film
+                .tone_mapped(tone_mapping)
+                .iter()
+                .flat_map(Color::to_rgb8)
+                .collect();
+            let img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+                ImageBuffer::from_raw(width, height, data).unwrap();
+            img.save_with_format(path, image::ImageFormat::Png)?;
+        }
+        OutputFormat::Png16 => {
+            let data = film
+                .tone_mapped(tone_mapping)
+                .iter()
+                .flat_map(Color::to_rgb16)
+                .collect();
+            let img: ImageBuffer<Rgb<u16>, Vec<u16>> =
+                ImageBuffer::from_raw(width, height, data).unwrap();
+            img.save_with_format(path, image::ImageFormat::Png)?;
+        }
+        OutputFormat::Hdr => {
+            let data: Vec<Rgb<f32>> = film.radiance().iter().map(|c| Rgb(c.to_rgb32f())).collect();
+            let w = BufWriter::new(File::create(path)?);
+            HdrEncoder::new(w).encode(&data, width as usize, height as usize)?;
+        }
+        OutputFormat::Exr if !film.aovs().is_empty() => save_exr_layers(film, path)?,
+        OutputFormat::Exr => {
+            let data = film.radiance().iter().flat_map(Color::to_rgb32f).collect();
+            let img: ImageBuffer<Rgb<f32>, Vec<f32>> =
+                ImageBuffer::from_raw(width, height, data).unwrap();
+            img.save_with_format(path, image::ImageFormat::OpenExr)?;
+        }
+    }
+    Ok(())
+}
+
+/// save_exr_layers writes the film with its AOVs to a single EXR file. Channels of AOVs are
+/// named like `normal.X`, except for alpha which is the `A` channel of the image.
+fn save_exr_layers(film: &Film, path: &Path) -> Result<(), OutputError> {
+    use exr::prelude::*;
+
+    let channel = |name: &str, values: &[Color], c: usize| {
+        let samples = values.iter().map(|v| v.to_rgb32f()[c]).collect();
+        AnyChannel::new(name, FlatSamples::F32(samples))
+    };
+    let radiance = film.radiance();
+    let mut channels: Vec<_> = ["R", "G", "B"]
+        .iter()
+        .enumerate()
+        .map(|(c, name)| channel(name, &radiance, c))
+        .collect();
+    for &aov in film.aovs() {
+        let values = film.aov(aov).unwrap();
+        for (c, name) in aov.channels().iter().enumerate() {
+            let name = match aov {
+                Aov::Alpha => name.to_string(),
+                _ => format!("{}.{}", aov.name(), name),
+            };
+            channels.push(channel(&name, &values, c));
+        }
+    }
+
+    let size = (film.width() as usize, film.height() as usize);
+    let layer = Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(SmallVec::from_vec(channels)),
+    );
+    Image::from_layer(layer).write().to_file(path)?;
+    Ok(())
+}
+
+/// aov_path returns where an AOV is written as a separate image, e.g. `image.depth.png`.
+pub fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(".");
+    name.push(aov.name());
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+/// save_aovs writes every AOV of the film as a separate image next to `path`. HDR images hold
+/// the values, PNG images show them: depth and position are scaled to [0, 1], normals mapped
+/// from [-1, 1] and ids given random colors. EXR files keep AOVs as layers instead.
+pub fn save_aovs(
+    film: &Film,
+    path: impl AsRef<Path>,
+    format: OutputFormat,
+) -> Result<(), OutputError> {
+    if format == OutputFormat::Exr {
+        return Ok(());
+    }
+    let path = path.as_ref();
+    let (width, height) = (film.width(), film.height());
+    for &aov in film.aovs() {
+        let aov_path = aov_path(path, aov);
+        let values = film.aov(aov).unwrap();
+        if format.is_hdr() {
+            let data: Vec<Rgb<f32>> = values.iter().map(|c| Rgb(c.to_rgb32f())).collect();
+            let w = BufWriter::new(File::create(&aov_path)?);
+            HdrEncoder::new(w).encode(&data, width as usize, height as usize)?;
+            continue;
+        }
+
+        let shown = show_aov(aov, &values);
+        if format == OutputFormat::Png16 {
+            let data = shown.iter().flat_map(Color::to_rgb16).collect();
+            let img: ImageBuffer<Rgb<u16>, Vec<u16>> =
+                ImageBuffer::from_raw(width, height, data).unwrap();
+            img.save_with_format(&aov_path, image::ImageFormat::Png)?;
+        } else {
+            let data = shown.iter().flat_map(Color::to_rgb8).collect();
+            let img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+                ImageBuffer::from_raw(width, height, data).unwrap();
+            img.save_with_format(&aov_path, image::ImageFormat::Png)?;
+        }
+    }
+    Ok(())
+}
+
+/// show_aov maps values of an AOV to display-referred colors.
+fn show_aov(aov: Aov, values: &[Color]) -> Vec<Color> {
+    // Scale every component to the range of its values.
+    let normalize = |values: &[Color]| -> Vec<Color> {
+        let fold = |f: fn(f64, f64) -> f64, init: f64| {
+            values.iter().fold(Color::new(init, init, init), |a, c| {
+                Color::new(f(a.r(), c.r()), f(a.g(), c.g()), f(a.b(), c.b()))
+            })
+        };
+        let (min, max) = (
+            fold(f64::min, f64::INFINITY),
+            fold(f64::max, f64::NEG_INFINITY),
+        );
+        let scale = |x: f64, min: f64, max: f64| {
+            if max > min {
+                (x - min) / (max - min)
+            } else {
+                0.0
+            }
+        };
+        values
+            .iter()
+            .map(|c| {
+                Color::new(
+                    scale(c.r(), min.r(), max.r()),
+                    scale(c.g(), min.g(), max.g()),
+                    scale(c.b(), min.b(), max.b()),
+                )
+            })
+            .collect()
+    };
+
+    match aov {
+        // Depth keeps 0 for the background and scales by the farthest surface.
+        Aov::Depth => {
+            let max = values.iter().map(|c| c.r()).fold(0.0, f64::max);
+            values
+                .iter()
+                .map(|c| if max > 0.0 { *c / max } else { *c })
+                .collect()
+        }
+        Aov::Normal => values
+            .iter()
+            .map(|&n| 0.5 * n + Color::new(0.5, 0.5, 0.5))
+            .collect(),
+        Aov::Albedo => values.iter().map(Color::to_srgb).collect(),
+        Aov::Position => normalize(values),
+        Aov::ObjectId | Aov::MaterialId => values
+            .iter()
+            .map(|c| match c.r() as u64 {
+                0 => Color::default(),
+                id => {
+                    let h = |i| 0.2 + 0.8 * hashed_1d(hash(id, i));
+                    Color::new(h(0), h(1), h(2))
+                }
+            })
+            .collect(),
+        Aov::Uv | Aov::Alpha => values.to_vec(),
+    }
+}
+
+/// save_sample_heatmap writes the number of samples of every pixel as a PNG image, from black
+/// for the fewest samples through red to yellow for the most.
+pub fn save_sample_heatmap(film: &Film, path: impl AsRef<Path>) -> Result<(), OutputError> {
+    // HEATMAP holds evenly spaced stops of the color ramp in sRGB.
+    const HEATMAP: [[f64; 3]; 5] = [
+        [0.0, 0.0, 0.02],
+        [0.34, 0.06, 0.43],
+        [0.73, 0.21, 0.33],
+        [0.98, 0.55, 0.04],
+        [0.99, 1.0, 0.64],
+    ];
+
+    let counts = film.sample_counts();
+    let min = counts.iter().copied().min().unwrap_or(0);
+    let max = counts.iter().copied().max().unwrap_or(0);
+    let data = counts
+        .iter()
+        .flat_map(|&n| {
+            let t = if max > min {
+                (n - min) as f64 / (max - min) as f64
+            } else {
+                0.0
+            };
+            let x = t * (HEATMAP.len() - 1) as f64;
+            let i = (x as usize).min(HEATMAP.len() - 2);
+            let f = x - i as f64;
+            let [a, b] = [HEATMAP[i], HEATMAP[i + 1]].map(|c| Color::new(c[0], c[1], c[2]));
+            (a * (1.0 - f) + b * f).to_rgb8()
+        })
+        .collect();
+    let img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+        ImageBuffer::from_raw(film.width(), film.height(), data).unwrap();
+    img.save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let format = |name: &str| OutputFormat::from_path(Path::new(name)).ok();
+        assert_eq!(format("image.png"), Some(OutputFormat::Png));
+        assert_eq!(format("image.PNG"), Some(OutputFormat::Png));
+        assert_eq!(format("image.hdr"), Some(OutputFormat::Hdr));
+        assert_eq!(format("image.exr"), Some(OutputFormat::Exr));
+        assert_eq!(format("image.jpg"), None);
+        assert_eq!(format("image"), None);
+
+        assert_eq!(OutputFormat::Png.with_bit_depth(16), OutputFormat::Png16);
+        assert_eq!(OutputFormat::Png16.with_bit_depth(8), OutputFormat::Png);
+        assert_eq!(OutputFormat::Exr.with_bit_depth(16), OutputFormat::Exr);
+    }
+
+    #[test]
+    fn png16_round_trip() {
+        let mut film = Film::new(3, 2);
+        for (i, c) in [0.0, 0.001, 0.25, 0.5, 0.75, 1.0].into_iter().enumerate() {
+            film.add_sample(i as u32 % 3, i as u32 / 3, Color::new(c, 1.0 - c, 0.5));
+        }
+        let path = std::env::temp_dir().join(format!("rayst-{}.png16.png", std::process::id()));
+        let format = OutputFormat::from_path(&path).unwrap().with_bit_depth(16);
+        save_with_format(&film, &path, format, &ToneMapping::default()).unwrap();
+        let img = image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let img = img.as_rgb16().expect("16-bit RGB image");
+        let expected: Vec<u16> = film
+            .tone_mapped(&ToneMapping::default())
+            .iter()
+            .flat_map(Color::to_rgb16)
+            .collect();
+        assert_eq!(img.as_raw(), &expected);
+        // Dark values keep detail 8 bits can't hold.
+        assert!(expected.iter().any(|&x| x % 256 != 0));
+    }
+}
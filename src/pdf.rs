@@ -0,0 +1,139 @@
+use std::f64::consts::PI;
+
+use crate::{
+    light::LightList,
+    sampler::Sampler,
+    vec3::{sample_cosine_direction, sample_unit_sphere, Onb, Point, Vec3},
+};
+
+/// Pdf is a probability density over directions which can also be sampled.
+pub trait Pdf {
+    /// value is the density of the unit direction `dir` with respect to the solid angle.
+    fn value(&self, dir: Vec3) -> f64;
+
+    /// generate samples a unit direction, `None` means that sampling failed.
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3>;
+}
+
+/// UniformPdf samples the whole sphere of directions.
+pub struct UniformPdf;
+
+impl Pdf for UniformPdf {
+    fn value(&self, _dir: Vec3) -> f64 {
+        0.25 / PI
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        Some(sample_unit_sphere(sampler.get_2d()))
+    }
+}
+
+/// CosinePdf samples the hemisphere around a normal proportionally to the cosine.
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3) -> Self {
+        Self {
+            uvw: Onb::from_w(normal),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, dir: Vec3) -> f64 {
+        let cosine = dir.dot(self.uvw.w);
+        cosine.max(0.0) / PI
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        Some(self.uvw.local(sample_cosine_direction(sampler.get_2d())))
+    }
+}
+
+/// HittablePdf samples directions towards lights as seen from `origin`.
+pub struct HittablePdf<'a> {
+    lights: &'a LightList,
+    origin: Point,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(lights: &'a LightList, origin: Point) -> Self {
+        Self { lights, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, dir: Vec3) -> f64 {
+        self.lights.pdf_value(self.origin, dir)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        self.lights.sample(sampler, self.origin).map(|s| s.wi)
+    }
+}
+
+/// MixturePdf picks `a` with probability `weight` and `b` otherwise.
+pub struct MixturePdf<'a> {
+    a: &'a dyn Pdf,
+    b: &'a dyn Pdf,
+    weight: f64,
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(a: &'a dyn Pdf, b: &'a dyn Pdf) -> Self {
+        Self::with_weight(a, b, 0.5)
+    }
+
+    pub fn with_weight(a: &'a dyn Pdf, b: &'a dyn Pdf, weight: f64) -> Self {
+        Self { a, b, weight }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, dir: Vec3) -> f64 {
+        self.weight * self.a.value(dir) + (1.0 - self.weight) * self.b.value(dir)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        if sampler.get_1d() < self.weight {
+            self.a.generate(sampler)
+        } else {
+            self.b.generate(sampler)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    /// integral estimates the integral of the density over the sphere.
+    fn integral(pdf: &dyn Pdf, sampler: &mut dyn Sampler) -> f64 {
+        const N: usize = 100_000;
+        let sum: f64 = (0..N)
+            .map(|_| pdf.value(sample_unit_sphere(sampler.get_2d())))
+            .sum();
+        sum * 4.0 * PI / N as f64
+    }
+
+    #[test]
+    fn normalized() {
+        let mut sampler = IndependentSampler::new(42);
+        let cosine = CosinePdf::new(Vec3::new(1.0, 2.0, 3.0));
+        let uniform = UniformPdf;
+        let mixture = MixturePdf::with_weight(&cosine, &uniform, 0.3);
+
+        for pdf in [&cosine as &dyn Pdf, &uniform, &mixture] {
+            assert!((integral(pdf, &mut sampler) - 1.0).abs() < 0.02);
+        }
+
+        for _ in 0..100 {
+            let dir = cosine.generate(&mut sampler).unwrap();
+            assert!((dir.length() - 1.0).abs() < 1e-9);
+            assert!(cosine.value(dir) > 0.0);
+        }
+    }
+}
@@ -1,15 +1,28 @@
 use crate::vec3::{Point, Vec3};
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Ray {
     pub orig: Point,
     pub dir: Vec3,
     pub time: f64,
+    // sample is a uniform number integrators draw from their sampler for every ray they trace,
+    // participating media decide where the ray scatters by it.
+    pub sample: f64,
 }
 
 impl Ray {
     pub fn new(orig: Point, dir: Vec3, time: f64) -> Self {
-        Self { orig, dir, time }
+        Self {
+            orig,
+            dir,
+            time,
+            sample: 0.0,
+        }
+    }
+
+    pub fn with_sample(mut self, sample: f64) -> Self {
+        self.sample = sample;
+        self
     }
 
     pub fn at(&self, t: f64) -> Point {
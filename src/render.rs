@@ -0,0 +1,1063 @@
+use std::ops::ControlFlow;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use crate::{
+    aov::Aov,
+    camera::Camera,
+    color::Color,
+    denoise::Denoiser,
+    film::{Film, Pixel},
+    filter::{Filter, FilterKind},
+    hittable::{HitRecord, Hittable},
+    light::LightList,
+    material::Material,
+    ray::Ray,
+    sampler::{Sampler, SamplerKind},
+};
+
+/// RenderSettings describes the image to produce.
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    // samples_per_pixel is the number of samples of every pixel, or the maximum number
+    // with adaptive sampling.
+    pub samples_per_pixel: u32,
+    // adaptive_threshold enables adaptive sampling when positive: pixels stop being sampled
+    // once their relative error is below it, but not before min_samples_per_pixel samples.
+    pub adaptive_threshold: f64,
+    pub min_samples_per_pixel: u32,
+    // max_depth is a hard limit on the number of bounces, paths are usually ended
+    // earlier by Russian roulette which starts after rr_min_depth bounces.
+    pub max_depth: u32,
+    pub rr_min_depth: u32,
+    // threads is the number of worker threads, 0 means all available cores.
+    pub threads: usize,
+    pub tile_size: u32,
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    // filter reconstructs pixels from samples, filter_radius overrides its default radius.
+    pub filter: FilterKind,
+    pub filter_radius: Option<f64>,
+    // denoise filters the finished image, guided by albedo and normal AOVs.
+    pub denoise: bool,
+    // media makes integrators draw a sample for participating media along every ray,
+    // scenes set it when they contain any.
+    pub media: bool,
+}
+
+impl RenderSettings {
+    /// filter builds the reconstruction filter.
+    pub fn filter(&self) -> Filter {
+        Filter::new(
+            self.filter,
+            self.filter_radius
+                .unwrap_or_else(|| self.filter.default_radius()),
+        )
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 400,
+            height: 225,
+            samples_per_pixel: 100,
+            adaptive_threshold: 0.0,
+            min_samples_per_pixel: 16,
+            max_depth: 50,
+            rr_min_depth: RR_MIN_DEPTH,
+            threads: 0,
+            tile_size: 32,
+            seed: 42,
+            sampler: SamplerKind::default(),
+            filter: FilterKind::default(),
+            filter_radius: None,
+            denoise: false,
+            media: false,
+        }
+    }
+}
+
+/// RR_MIN_DEPTH is the default number of bounces made before Russian roulette may end a path.
+pub const RR_MIN_DEPTH: u32 = 3;
+
+/// russian_roulette randomly ends paths after `min_depth` bounces with a probability growing as
+/// their throughput falls. Surviving paths are reweighted so the estimate stays unbiased.
+fn russian_roulette(
+    sampler: &mut dyn Sampler,
+    throughput: &mut Color,
+    depth: u32,
+    min_depth: u32,
+) -> bool {
+    if depth < min_depth {
+        return true;
+    }
+    let p = throughput.max_component().min(0.95);
+    if p <= 0.0 || sampler.get_1d() >= p {
+        return false;
+    }
+    *throughput = *throughput / p;
+    true
+}
+
+/// media_sample draws the sample of a ray crossing participating media, scenes without media
+/// don't spend a sampler dimension on it.
+fn media_sample(sampler: &mut dyn Sampler, media: bool) -> f64 {
+    if media {
+        sampler.get_1d()
+    } else {
+        0.0
+    }
+}
+
+/// Integrator computes radiance arriving along a camera ray. It sets `first_hit` to the
+/// surface the camera ray hits, if any, which the renderer takes AOVs from.
+pub trait Integrator: Send + Sync {
+    fn li<'w>(
+        &self,
+        sampler: &mut dyn Sampler,
+        r: &Ray,
+        world: &'w dyn Hittable,
+        background: Color,
+        first_hit: &mut Option<HitRecord<'w>>,
+    ) -> Color;
+}
+
+/// PathTracer is a path tracer which relies solely on `Material::scatter`.
+pub struct PathTracer {
+    pub max_depth: u32,
+    pub rr_min_depth: u32,
+    // media draws a sample for participating media along every ray.
+    pub media: bool,
+}
+
+impl PathTracer {
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth,
+            rr_min_depth: RR_MIN_DEPTH,
+            media: false,
+        }
+    }
+
+    pub fn with_russian_roulette(mut self, min_depth: u32) -> Self {
+        self.rr_min_depth = min_depth;
+        self
+    }
+
+    pub fn with_media(mut self, media: bool) -> Self {
+        self.media = media;
+        self
+    }
+}
+
+impl Integrator for PathTracer {
+    fn li<'w>(
+        &self,
+        sampler: &mut dyn Sampler,
+        r: &Ray,
+        world: &'w dyn Hittable,
+        background: Color,
+        first_hit: &mut Option<HitRecord<'w>>,
+    ) -> Color {
+        let mut radiance = Color::default();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = r.clone();
+
+        for depth in 0..self.max_depth {
+            ray.sample = media_sample(sampler, self.media);
+            let Some(rec) = world.hit(&ray, 0.001, f64::INFINITY) else {
+                radiance += throughput * background;
+                break;
+            };
+            if depth == 0 {
+                *first_hit = Some(rec.clone());
+            }
+
+            radiance += throughput * rec.material.emitted(rec.u, rec.v, &rec.p);
+            let Some((scattered, attenuation)) = rec.material.scatter(sampler, &ray, &rec) else {
+                break;
+            };
+
+            throughput = throughput * attenuation;
+            if !russian_roulette(sampler, &mut throughput, depth, self.rr_min_depth) {
+                break;
+            }
+            ray = scattered;
+        }
+
+        radiance
+    }
+}
+
+/// NeePathTracer is a path tracer with next event estimation: at every non-specular bounce
+/// it samples a light and traces a shadow ray towards it.
+pub struct NeePathTracer {
+    pub max_depth: u32,
+    pub rr_min_depth: u32,
+    // media draws a sample for participating media along every ray.
+    pub media: bool,
+    pub lights: LightList,
+}
+
+impl NeePathTracer {
+    pub fn new(max_depth: u32, lights: LightList) -> Self {
+        Self {
+            max_depth,
+            rr_min_depth: RR_MIN_DEPTH,
+            media: false,
+            lights,
+        }
+    }
+
+    pub fn with_russian_roulette(mut self, min_depth: u32) -> Self {
+        self.rr_min_depth = min_depth;
+        self
+    }
+
+    pub fn with_media(mut self, media: bool) -> Self {
+        self.media = media;
+        self
+    }
+
+    /// direct estimates light arriving at `rec` directly from sampled lights.
+    fn direct(
+        &self,
+        sampler: &mut dyn Sampler,
+        r: &Ray,
+        rec: &HitRecord,
+        world: &dyn Hittable,
+    ) -> Option<Color> {
+        let sample = self.lights.sample(sampler, rec.p)?;
+        let f = rec.material.eval(r, rec, sample.wi);
+        if sample.pdf <= 0.0 || f.luminance() <= 0.0 {
+            return None;
+        }
+
+        let dist = (sample.p - rec.p).length();
+        let shadow =
+            Ray::new(rec.p, sample.wi, r.time).with_sample(media_sample(sampler, self.media));
+        if world.hit(&shadow, 0.001, dist * (1.0 - 1e-4)).is_some() {
+            return None;
+        }
+        Some(f * sample.emitted / sample.pdf)
+    }
+}
+
+impl Integrator for NeePathTracer {
+    fn li<'w>(
+        &self,
+        sampler: &mut dyn Sampler,
+        r: &Ray,
+        world: &'w dyn Hittable,
+        background: Color,
+        first_hit: &mut Option<HitRecord<'w>>,
+    ) -> Color {
+        let mut radiance = Color::default();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = r.clone();
+        // Emission of sampled lights is counted only when it couldn't be sampled
+        // at the previous vertex, i.e. for camera rays and after specular bounces.
+        let mut specular = true;
+
+        for depth in 0..self.max_depth {
+            ray.sample = media_sample(sampler, self.media);
+            let Some(rec) = world.hit(&ray, 0.001, f64::INFINITY) else {
+                radiance += throughput * background;
+                break;
+            };
+            if depth == 0 {
+                *first_hit = Some(rec.clone());
+            }
+
+            if specular || !self.lights.samples(rec.material) {
+                radiance += throughput * rec.material.emitted(rec.u, rec.v, &rec.p);
+            }
+
+            let Some(scattered) = rec.material.sample(sampler, &ray, &rec) else {
+                break;
+            };
+
+            specular = scattered.pdf.is_none();
+            if !specular {
+                if let Some(direct) = self.direct(sampler, &ray, &rec, world) {
+                    radiance += throughput * direct;
+                }
+            }
+
+            throughput = throughput * scattered.attenuation;
+            if !russian_roulette(sampler, &mut throughput, depth, self.rr_min_depth) {
+                break;
+            }
+            ray = scattered.ray;
+        }
+
+        radiance
+    }
+}
+
+/// Heuristic weights samples of one strategy against another in multiple importance sampling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Heuristic {
+    Balance,
+    Power,
+}
+
+impl Heuristic {
+    /// weight of a sample with density `pdf` under its strategy and `other` under the other one.
+    pub fn weight(self, pdf: f64, other: f64) -> f64 {
+        let (a, b) = match self {
+            Self::Balance => (pdf, other),
+            Self::Power => (pdf * pdf, other * other),
+        };
+        if a + b > 0.0 {
+            a / (a + b)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// MisPathTracer samples both materials and lights at every non-specular bounce
+/// and combines them with multiple importance sampling.
+pub struct MisPathTracer {
+    pub max_depth: u32,
+    pub rr_min_depth: u32,
+    // media draws a sample for participating media along every ray.
+    pub media: bool,
+    pub lights: LightList,
+    pub heuristic: Heuristic,
+}
+
+impl MisPathTracer {
+    pub fn new(max_depth: u32, lights: LightList) -> Self {
+        Self {
+            max_depth,
+            rr_min_depth: RR_MIN_DEPTH,
+            media: false,
+            lights,
+            heuristic: Heuristic::Power,
+        }
+    }
+
+    pub fn with_russian_roulette(mut self, min_depth: u32) -> Self {
+        self.rr_min_depth = min_depth;
+        self
+    }
+
+    pub fn with_media(mut self, media: bool) -> Self {
+        self.media = media;
+        self
+    }
+
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    /// direct estimates light arriving at `rec` from a sampled light weighted against material sampling.
+    fn direct(
+        &self,
+        sampler: &mut dyn Sampler,
+        r: &Ray,
+        rec: &HitRecord,
+        world: &dyn Hittable,
+    ) -> Option<Color> {
+        let sample = self.lights.sample(sampler, rec.p)?;
+        let f = rec.material.eval(r, rec, sample.wi);
+        if sample.pdf <= 0.0 || f.luminance() <= 0.0 {
+            return None;
+        }
+
+        let dist = (sample.p - rec.p).length();
+        let shadow =
+            Ray::new(rec.p, sample.wi, r.time).with_sample(media_sample(sampler, self.media));
+        if world.hit(&shadow, 0.001, dist * (1.0 - 1e-4)).is_some() {
+            return None;
+        }
+
+        let weight = self
+            .heuristic
+            .weight(sample.pdf, rec.material.pdf(r, rec, sample.wi));
+        Some(f * sample.emitted * (weight / sample.pdf))
+    }
+}
+
+impl Integrator for MisPathTracer {
+    fn li<'w>(
+        &self,
+        sampler: &mut dyn Sampler,
+        r: &Ray,
+        world: &'w dyn Hittable,
+        background: Color,
+        first_hit: &mut Option<HitRecord<'w>>,
+    ) -> Color {
+        let mut radiance = Color::default();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = r.clone();
+        // prev is the material density of the current ray,
+        // `None` for camera rays and after specular bounces.
+        let mut prev: Option<f64> = None;
+
+        for depth in 0..self.max_depth {
+            ray.sample = media_sample(sampler, self.media);
+            let Some(rec) = world.hit(&ray, 0.001, f64::INFINITY) else {
+                radiance += throughput * background;
+                break;
+            };
+            if depth == 0 {
+                *first_hit = Some(rec.clone());
+            }
+
+            let emitted = rec.material.emitted(rec.u, rec.v, &rec.p);
+            let weight = match prev {
+                Some(pdf) if self.lights.samples(rec.material) => {
+                    self.heuristic.weight(pdf, self.lights.pdf_hit(&ray))
+                }
+                _ => 1.0,
+            };
+            radiance += throughput * emitted * weight;
+
+            let Some(scattered) = rec.material.sample(sampler, &ray, &rec) else {
+                break;
+            };
+
+            prev = scattered.pdf;
+            if prev.is_some() {
+                if let Some(direct) = self.direct(sampler, &ray, &rec, world) {
+                    radiance += throughput * direct;
+                }
+            }
+
+            throughput = throughput * scattered.attenuation;
+            if !russian_roulette(sampler, &mut throughput, depth, self.rr_min_depth) {
+                break;
+            }
+            ray = scattered.ray;
+        }
+
+        radiance
+    }
+}
+
+/// DENOISER_AOVS are the AOVs guiding the denoiser.
+pub const DENOISER_AOVS: [Aov; 2] = [Aov::Albedo, Aov::Normal];
+
+/// Renderer renders a world as seen by a camera onto a film.
+pub struct Renderer<'a> {
+    world: &'a dyn Hittable,
+    camera: &'a Camera,
+    background: Color,
+    settings: RenderSettings,
+    filter: Filter,
+    integrator: Box<dyn Integrator + 'a>,
+    materials: &'a [Arc<dyn Material>],
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(
+        world: &'a dyn Hittable,
+        camera: &'a Camera,
+        background: Color,
+        settings: RenderSettings,
+    ) -> Self {
+        let integrator = Box::new(
+            PathTracer::new(settings.max_depth)
+                .with_russian_roulette(settings.rr_min_depth)
+                .with_media(settings.media),
+        );
+        Self {
+            world,
+            camera,
+            background,
+            filter: settings.filter(),
+            settings,
+            integrator,
+            materials: &[],
+        }
+    }
+
+    /// with_integrator replaces the default `PathTracer`.
+    pub fn with_integrator(mut self, integrator: impl Integrator + 'a) -> Self {
+        self.integrator = Box::new(integrator);
+        self
+    }
+
+    /// with_materials numbers materials for material id AOVs.
+    pub fn with_materials(mut self, materials: &'a [Arc<dyn Material>]) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// render renders the image, which is denoised if the settings ask for it.
+    pub fn render(&self) -> Film {
+        let mut film =
+            Film::new(self.settings.width, self.settings.height).with_filter(self.filter.clone());
+        if self.settings.denoise {
+            film = film.with_aovs(&DENOISER_AOVS);
+        }
+        self.render_into(
+            &mut film,
+            None,
+            |_, _, _| {},
+            |_, _| ControlFlow::Continue(()),
+        );
+        if self.settings.denoise {
+            film = Denoiser::new().denoise(&film);
+        }
+        film
+    }
+
+    /// render_into adds samples to `film`, which may hold samples of an earlier render
+    /// of the same scene, until every pixel has `samples_per_pixel` or adaptive sampling
+    /// considers it converged. The image is rendered in passes taking more samples of
+    /// every pixel, AOVs kept by the film are computed along. `progress(samples, done, total)`
+    /// is called when a tile of the pass up to `samples` per pixel is finished,
+    /// `on_pass(film, samples)` after the whole pass.
+    ///
+    /// It returns false if rendering was stopped by `deadline` or `on_pass` before finishing.
+    pub fn render_into(
+        &self,
+        film: &mut Film,
+        deadline: Option<Instant>,
+        mut progress: impl FnMut(u32, usize, usize),
+        mut on_pass: impl FnMut(&Film, u32) -> ControlFlow<()>,
+    ) -> bool {
+        let RenderSettings {
+            width,
+            height,
+            threads,
+            tile_size,
+            ..
+        } = self.settings;
+        assert_eq!((film.width(), film.height()), (width, height));
+
+        let threads = match threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let tiles = split_into_tiles(width, height, tile_size.max(1));
+        let aovs = film.aovs().to_vec();
+        let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+        loop {
+            let counts = film.sample_counts();
+            let end = self.pass_end(counts.iter().copied().max().unwrap_or(0));
+            let active = self.active_pixels(film, end);
+            if !active.contains(&true) {
+                return true;
+            }
+            if expired() {
+                return false;
+            }
+
+            let next_tile = AtomicUsize::new(0);
+            thread::scope(|s| {
+                let (tx, rx) = mpsc::channel();
+                for _ in 0..threads {
+                    let tx = tx.clone();
+                    let (tiles, next_tile, counts, active, aovs) =
+                        (&tiles, &next_tile, &counts, &active, &aovs);
+                    s.spawn(move || loop {
+                        // Tiles which are not started in time are left for a later render.
+                        if expired() {
+                            break;
+                        }
+                        let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let Some(tile) = tiles.get(index) else {
+                            break;
+                        };
+                        let pixels = self.render_tile(tile, counts, end, active, aovs);
+                        if tx.send((index, pixels)).is_err() {
+                            break;
+                        }
+                    });
+                }
+                drop(tx);
+
+                for (done, (index, (pixels, aov_values))) in rx.iter().enumerate() {
+                    let tile = &tiles[index];
+                    let n = aovs.len();
+                    let mut pixels = pixels.iter().enumerate();
+                    for j in tile.y.clone() {
+                        for i in tile.x.clone() {
+                            let (k, px) = pixels.next().unwrap();
+                            let values = &aov_values[k * n..(k + 1) * n];
+                            // Rows are stored top to bottom, while `j` grows upwards.
+                            film.merge_pixel(i, height - 1 - j, px, values);
+                        }
+                    }
+                    progress(end, done + 1, tiles.len());
+                }
+            });
+
+            if on_pass(film, end).is_break() {
+                return false;
+            }
+        }
+    }
+
+    /// pass_end returns the number of samples per pixel the pass after `done` renders up to.
+    /// The number doubles every pass, starting from the minimum of adaptive sampling,
+    /// but passes take at most MAX_PASS_SAMPLES to give regular feedback.
+    fn pass_end(&self, done: u32) -> u32 {
+        const MAX_PASS_SAMPLES: u32 = 64;
+
+        let end = if done > 0 {
+            done + done.min(MAX_PASS_SAMPLES)
+        } else if self.settings.adaptive_threshold > 0.0 {
+            self.settings.min_samples_per_pixel.max(1)
+        } else {
+            1
+        };
+        end.min(self.settings.samples_per_pixel)
+    }
+
+    /// active_pixels marks pixels which still need samples before `end`, rows are stored top
+    /// to bottom. Adaptive sampling leaves out pixels whose error is already below the threshold.
+    fn active_pixels(&self, film: &Film, end: u32) -> Vec<bool> {
+        let RenderSettings {
+            width,
+            height,
+            adaptive_threshold,
+            min_samples_per_pixel,
+            ..
+        } = self.settings;
+
+        let mut active = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let samples = film.samples(x, y);
+                let converged = adaptive_threshold > 0.0
+                    && samples >= min_samples_per_pixel
+                    && film.relative_error(x, y) < adaptive_threshold;
+                active.push(samples < end && !converged);
+            }
+        }
+        active
+    }
+
+    /// render_tile takes samples of active pixels of the tile, continuing from the number
+    /// of samples in `counts` up to `end`. It returns the pixels and the values of `aovs`
+    /// over the same samples.
+    fn render_tile(
+        &self,
+        tile: &Tile,
+        counts: &[u32],
+        end: u32,
+        active: &[bool],
+        aovs: &[Aov],
+    ) -> (Vec<Pixel>, Vec<Color>) {
+        let RenderSettings {
+            width,
+            height,
+            samples_per_pixel,
+            ..
+        } = self.settings;
+
+        // Samplers derive every sample from the seed, the pixel and the sample index,
+        // which keeps the output independent of tiles and threads.
+        let mut sampler = self
+            .settings
+            .sampler
+            .build(samples_per_pixel, self.settings.seed);
+        let mut pixels = Vec::with_capacity(tile.len());
+        let n = aovs.len();
+        let mut aov_values = vec![Color::default(); tile.len() * n];
+        for j in tile.y.clone() {
+            for i in tile.x.clone() {
+                let mut px = Pixel::default();
+                let p = pixels.len();
+                let values = &mut aov_values[p * n..(p + 1) * n];
+                let k = ((height - 1 - j) * width + i) as usize;
+                if active[k] {
+                    for index in counts[k]..end {
+                        sampler.start_pixel_sample(i, j, index);
+                        // The filter picks where the sample is taken around the pixel center.
+                        let sample = self.filter.sample(sampler.get_2d());
+                        let (du, dv) = sample.offset;
+                        let u = (i as f64 + 0.5 + du) / width as f64;
+                        let v = (j as f64 + 0.5 + dv) / height as f64;
+                        let r = self.camera.get_ray(&mut *sampler, u, v);
+                        let mut first_hit = None;
+                        let li = self.integrator.li(
+                            &mut *sampler,
+                            &r,
+                            self.world,
+                            self.background,
+                            &mut first_hit,
+                        );
+                        px.add(li * sample.weight);
+                        if !aovs.is_empty() {
+                            self.add_aovs(aovs, values, px.samples, &r, first_hit.as_ref());
+                        }
+                    }
+                }
+                pixels.push(px);
+            }
+        }
+        (pixels, aov_values)
+    }
+
+    /// add_aovs adds the AOVs of camera ray `r` hitting `rec` first to the values over
+    /// `samples - 1` earlier samples.
+    fn add_aovs(
+        &self,
+        aovs: &[Aov],
+        values: &mut [Color],
+        samples: u32,
+        r: &Ray,
+        rec: Option<&HitRecord>,
+    ) {
+        for (aov, value) in aovs.iter().zip(values) {
+            let x = aov.evaluate(r, rec, self.materials);
+            if !aov.is_id() {
+                *value += (x - *value) / samples as f64;
+            } else if samples == 1 {
+                *value = x;
+            }
+        }
+    }
+}
+
+/// Tile is a rectangular block of pixels rendered by a single thread.
+struct Tile {
+    x: Range<u32>,
+    y: Range<u32>,
+}
+
+impl Tile {
+    fn len(&self) -> usize {
+        self.x.len() * self.y.len()
+    }
+}
+
+fn split_into_tiles(width: u32, height: u32, size: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(size as usize).rev() {
+        for x in (0..width).step_by(size as usize) {
+            tiles.push(Tile {
+                x: x..(x + size).min(width),
+                y: y..(y + size).min(height),
+            });
+        }
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        aarect::XZRect,
+        hittable_list::HittableList,
+        material::{DiffuseLight, Lambertian, Material, ScatterRecord},
+        sampler::IndependentSampler,
+        sphere::Sphere,
+        vec3::{Point, Vec3},
+    };
+
+    fn mean(integrator: &dyn Integrator, world: &dyn Hittable, r: &Ray, n: usize) -> f64 {
+        let mut sampler = IndependentSampler::new(1);
+        let mut sum = 0.0;
+        for _ in 0..n {
+            sum += integrator
+                .li(&mut sampler, r, world, Color::default(), &mut None)
+                .luminance();
+        }
+        sum / n as f64
+    }
+
+    // Glowing is a diffuse surface which also emits light.
+    struct Glowing(Lambertian);
+
+    impl Material for Glowing {
+        fn emitted(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+            Color::new(1.0, 1.0, 1.0)
+        }
+
+        fn sample(
+            &self,
+            sampler: &mut dyn Sampler,
+            r_in: &Ray,
+            rec: &HitRecord,
+        ) -> Option<ScatterRecord> {
+            self.0.sample(sampler, r_in, rec)
+        }
+    }
+
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        // Radiance inside a closed glowing sphere is the sum of emission over every bounce, 1 / (1 - albedo).
+        let material = Arc::new(Glowing(Lambertian::new(Color::new(0.8, 0.8, 0.8))));
+        let world = Sphere::new(Point::default(), 1.0, material);
+        let r = Ray::new(Point::default(), Vec3::new(0.0, 0.0, -1.0), 0.0);
+
+        let truncated = mean(
+            &PathTracer::new(50).with_russian_roulette(50),
+            &world,
+            &r,
+            10,
+        );
+        assert!((truncated - 5.0).abs() < 1e-3, "{}", truncated);
+
+        for min_depth in [0, 3] {
+            let actual = mean(
+                &PathTracer::new(1000).with_russian_roulette(min_depth),
+                &world,
+                &r,
+                50_000,
+            );
+            assert!((actual - 5.0).abs() < 0.1, "{}", actual);
+        }
+    }
+
+    #[test]
+    fn nee_matches_path_tracing() {
+        let light = Arc::new(XZRect::new(
+            -1.0,
+            1.0,
+            -1.0,
+            1.0,
+            2.0,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        ));
+        let mut world = HittableList::default();
+        world.add(Arc::new(XZRect::new(
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            0.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        world.add(light.clone());
+        let mut lights = LightList::default();
+        lights.add(light);
+
+        let r = Ray::new(Point::new(0.0, 1.0, 0.5), Vec3::new(0.0, -1.0, -0.5), 0.0);
+        let expected = mean(&PathTracer::new(5), &world, &r, 50_000);
+        let nee = mean(&NeePathTracer::new(5, lights.clone()), &world, &r, 5_000);
+        let mis = mean(&MisPathTracer::new(5, lights), &world, &r, 5_000);
+        for actual in [nee, mis] {
+            assert!(
+                (expected - actual).abs() < 0.03 * expected,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn independent_of_scheduling() {
+        // The smoke scene also covers the random scattering distance of media.
+        let mut scene =
+            crate::scene::load(std::path::Path::new("scenes/cornell_smoke.toml")).unwrap();
+        scene.settings.width = 24;
+        scene.settings.height = 24;
+        scene.settings.samples_per_pixel = 4;
+        let camera = scene.camera();
+
+        for sampler in [SamplerKind::Independent, SamplerKind::Sobol] {
+            let render = |threads: usize, tile_size: u32| {
+                let mut settings = scene.settings.clone();
+                settings.sampler = sampler;
+                settings.threads = threads;
+                settings.tile_size = tile_size;
+                Renderer::new(&*scene.world, &camera, scene.background, settings)
+                    .render()
+                    .radiance()
+            };
+            assert_eq!(render(1, 32), render(3, 7));
+        }
+    }
+
+    #[test]
+    fn adaptive_sampling() {
+        // A diffuse sphere under a uniform sky, pixels of the sky have no variance at all.
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let world = Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, material);
+        let camera = Camera::default();
+        let settings = RenderSettings {
+            width: 32,
+            height: 18,
+            samples_per_pixel: 256,
+            adaptive_threshold: 0.02,
+            min_samples_per_pixel: 8,
+            ..Default::default()
+        };
+        let film = Renderer::new(&world, &camera, Color::new(1.0, 1.0, 1.0), settings).render();
+
+        assert_eq!(film.samples(0, 0), 8);
+        assert_eq!(film.get_pixel(0, 0), Color::new(1.0, 1.0, 1.0));
+        let counts = film.sample_counts();
+        assert!(counts.iter().any(|&n| n > 8));
+        assert!(counts.iter().all(|&n| n <= 256));
+
+        // Pixels stop at the end of a pass, once their error is below the threshold.
+        for y in 0..film.height() {
+            for x in 0..film.width() {
+                let n = film.samples(x, y);
+                assert!(n.is_power_of_two() || n.is_multiple_of(64));
+                assert!(n == 256 || film.relative_error(x, y) < 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn aovs() {
+        let scene = crate::scene::load(std::path::Path::new("scenes/cornell_box.toml")).unwrap();
+        let camera = scene.camera();
+        let settings = |samples_per_pixel| RenderSettings {
+            width: 16,
+            height: 16,
+            samples_per_pixel,
+            ..scene.settings.clone()
+        };
+        let aovs = [Aov::Alpha, Aov::Depth, Aov::ObjectId, Aov::MaterialId];
+        let render = |film: &mut Film, samples_per_pixel| {
+            Renderer::new(
+                &*scene.world,
+                &camera,
+                scene.background,
+                settings(samples_per_pixel),
+            )
+            .with_materials(&scene.materials)
+            .render_into(film, None, |_, _, _| {}, |_, _| ControlFlow::Continue(()))
+        };
+
+        let mut film = Film::new(16, 16).with_aovs(&aovs);
+        render(&mut film, 4);
+        // The walls of the box reach the border of the image, so pixels inside see one of
+        // its 8 objects and 4 materials with every sample.
+        let inside = |aov| {
+            let values = film.aov(aov).unwrap();
+            (1..15)
+                .flat_map(|y| (1..15).map(move |x| y * 16 + x))
+                .map(|k| values[k].r())
+                .collect::<Vec<_>>()
+        };
+        assert!(inside(Aov::Alpha).iter().all(|&a| a == 1.0));
+        assert!(inside(Aov::Depth).iter().all(|&d| d > 0.0));
+        assert!(inside(Aov::ObjectId)
+            .iter()
+            .all(|&id| (1.0..=8.0).contains(&id)));
+        assert!(inside(Aov::MaterialId)
+            .iter()
+            .all(|&id| (1.0..=4.0).contains(&id)));
+
+        // Rendering in two parts gives the same AOVs.
+        let mut resumed = Film::new(16, 16).with_aovs(&aovs);
+        render(&mut resumed, 1);
+        render(&mut resumed, 4);
+        for aov in aovs {
+            let (a, b) = (film.aov(aov).unwrap(), resumed.aov(aov).unwrap());
+            for (a, b) in a.iter().zip(&b) {
+                assert!((*a - *b).map(f64::abs).max_component() < 1e-9, "{:?}", aov);
+            }
+        }
+    }
+
+    #[test]
+    fn resume() {
+        let scene = crate::scene::load(std::path::Path::new("scenes/two_spheres.toml")).unwrap();
+        let camera = scene.camera();
+        let settings = |samples_per_pixel| RenderSettings {
+            width: 16,
+            height: 9,
+            samples_per_pixel,
+            ..scene.settings.clone()
+        };
+
+        let expected =
+            Renderer::new(&*scene.world, &camera, scene.background, settings(8)).render();
+
+        // Stop after the second pass, then continue with more samples.
+        let mut film = Film::new(16, 9);
+        let renderer = Renderer::new(&*scene.world, &camera, scene.background, settings(4));
+        let mut passes = 0;
+        let finished = renderer.render_into(
+            &mut film,
+            None,
+            |_, _, _| {},
+            |_, samples| {
+                passes += 1;
+                assert_eq!(samples, passes);
+                if passes == 2 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        );
+        assert!(!finished);
+        assert!(film.sample_counts().iter().all(|&n| n == 2));
+
+        let renderer = Renderer::new(&*scene.world, &camera, scene.background, settings(8));
+        let finished = renderer.render_into(
+            &mut film,
+            None,
+            |_, _, _| {},
+            |_, _| ControlFlow::Continue(()),
+        );
+        assert!(finished);
+        assert_eq!(film.sample_counts(), expected.sample_counts());
+        for (a, b) in film.radiance().iter().zip(expected.radiance()) {
+            assert!((*a - b).map(f64::abs).max_component() < 1e-12);
+        }
+
+        // Nothing is rendered after the deadline.
+        let mut film = Film::new(16, 9);
+        let finished = renderer.render_into(
+            &mut film,
+            Some(Instant::now()),
+            |_, _, _| {},
+            |_, _| ControlFlow::Continue(()),
+        );
+        assert!(!finished);
+        assert!(film.sample_counts().iter().all(|&n| n == 0));
+    }
+
+    #[test]
+    fn mis_matches_path_tracing_on_cornell_box() {
+        let mut scene =
+            crate::scene::load(std::path::Path::new("scenes/cornell_box.toml")).unwrap();
+        scene.settings.width = 16;
+        scene.settings.height = 16;
+        let camera = scene.camera();
+
+        let image_mean = |spp: u32, integrator: Option<MisPathTracer>| {
+            let mut settings = scene.settings.clone();
+            settings.samples_per_pixel = spp;
+            let mut renderer = Renderer::new(&*scene.world, &camera, scene.background, settings);
+            if let Some(integrator) = integrator {
+                renderer = renderer.with_integrator(integrator);
+            }
+            let radiance = renderer.render().radiance();
+            radiance.iter().map(|c| c.luminance()).sum::<f64>() / radiance.len() as f64
+        };
+
+        let expected = image_mean(1024, None);
+        let mis = MisPathTracer::new(scene.settings.max_depth, scene.lights.clone());
+        let actual = image_mean(64, Some(mis));
+        assert!(
+            (expected - actual).abs() < 0.05 * expected,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+}
@@ -0,0 +1,519 @@
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+
+/// Sampler hands out the sample vector of a pixel sample one or two dimensions at a time.
+/// Consumers draw dimensions in the same order for every sample, so that low discrepancy
+/// samplers can spread the samples of a pixel evenly in each of them.
+pub trait Sampler {
+    /// start_pixel_sample begins sample `index` of pixel (`x`, `y`) at its first dimension.
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+
+    /// get_1d returns the next dimension of the current sample in [0, 1).
+    fn get_1d(&mut self) -> f64;
+
+    /// get_2d returns the next two dimensions of the current sample in [0, 1)^2.
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+/// SamplerKind selects a sampler in scene descriptions and on the command line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(Self::Independent),
+            "stratified" => Some(Self::Stratified),
+            "halton" => Some(Self::Halton),
+            "sobol" => Some(Self::Sobol),
+            "blue-noise" => Some(Self::BlueNoise),
+            _ => None,
+        }
+    }
+
+    /// is_progressive returns true for samplers whose samples don't depend on the number of
+    /// samples per pixel, so that renders can be continued with more samples.
+    pub fn is_progressive(self) -> bool {
+        !matches!(self, Self::Stratified | Self::BlueNoise)
+    }
+
+    /// build creates a sampler for `samples_per_pixel` samples of every pixel.
+    pub fn build(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            Self::Independent => Box::new(IndependentSampler::new(seed)),
+            Self::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            Self::Halton => Box::new(HaltonSampler::new(seed)),
+            Self::Sobol => Box::new(SobolSampler::new(seed)),
+            Self::BlueNoise => Box::new(BlueNoiseSampler::new(samples_per_pixel, seed)),
+        }
+    }
+}
+
+/// IndependentSampler draws every dimension from a random generator, which is reseeded
+/// from a hash of the seed, the pixel and the sample index at the start of every sample.
+pub struct IndependentSampler {
+    seed: u64,
+    rng: crate::Rng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: crate::Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        let pixel = hash(self.seed, (x as u64) << 32 | y as u64);
+        self.rng = crate::Rng::seed_from_u64(hash(pixel, index as u64));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
+
+/// StratifiedSampler splits every dimension into one stratum per sample and jitters samples
+/// inside of them. The strata of a dimension are visited in a random order per pixel.
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    state: PixelSample,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: PixelSample::new(seed),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.state.next(1);
+        let n = self.samples_per_pixel;
+        let stratum = permutation_element(self.state.index % n, n, h as u32);
+        let jitter = hashed_1d(hash(h, self.state.index as u64));
+        (stratum as f64 + jitter) / n as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.state.next(2);
+        let n = self.samples_per_pixel;
+        // The grid has at least as many cells as samples, some are left empty unless n is a square.
+        let nx = (n as f64).sqrt() as u32;
+        let ny = n.div_ceil(nx);
+        let cell = permutation_element(self.state.index % n, nx * ny, h as u32);
+        let jitter = hash(h, self.state.index as u64);
+        (
+            ((cell % nx) as f64 + hashed_1d(jitter)) / nx as f64,
+            ((cell / nx) as f64 + hashed_1d(mix_bits(jitter))) / ny as f64,
+        )
+    }
+}
+
+/// HaltonSampler uses the Halton sequence, where dimension `d` is the radical inverse in the
+/// d-th prime base. Digits are Owen scrambled with a different seed for every pixel.
+pub struct HaltonSampler {
+    state: PixelSample,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelSample::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dim = self.state.dim as usize;
+        let h = self.state.next(1);
+        match PRIMES.get(dim) {
+            Some(&base) => owen_scrambled_radical_inverse(base as u64, self.state.index as u64, h),
+            // Large bases are poorly distributed anyway.
+            None => hashed_1d(hash(h, self.state.index as u64)),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// SobolSampler pads the sample vector with pairs of dimensions of the 2D Sobol sequence.
+/// Every pair shuffles the sample order and Owen scrambles the points with its own seed,
+/// which keeps the samples of a pixel stratified for power of two sample counts.
+pub struct SobolSampler {
+    state: PixelSample,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelSample::new(seed),
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.state.next(1);
+        let index = nested_uniform_scramble(self.state.index, h as u32);
+        to_unit(nested_uniform_scramble(
+            index.reverse_bits(),
+            (h >> 32) as u32,
+        ))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.state.next(2);
+        let index = nested_uniform_scramble(self.state.index, h as u32);
+        let (x, y) = sobol_2d(index);
+        (
+            to_unit(nested_uniform_scramble(x, (h >> 32) as u32)),
+            to_unit(nested_uniform_scramble(y, mix_bits(h) as u32)),
+        )
+    }
+}
+
+/// BlueNoiseSampler uses the R2 rank-1 lattice for every pair of dimensions, shifted per pixel
+/// by the R2 dither mask. Neighbouring pixels get very different shifts, which pushes the
+/// remaining error towards high frequencies where it is less visible.
+pub struct BlueNoiseSampler {
+    samples_per_pixel: u32,
+    state: PixelSample,
+    shift: (f64, f64),
+}
+
+impl BlueNoiseSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: PixelSample::new(seed),
+            shift: (0.0, 0.0),
+        }
+    }
+
+    /// index shuffles sample indices per dimension to decorrelate dimensions from each other.
+    fn index(&self, h: u64) -> f64 {
+        let n = self.samples_per_pixel;
+        let index = self.state.index;
+        (index - index % n + permutation_element(index % n, n, h as u32)) as f64
+    }
+}
+
+// R2 holds the inverses of the first powers of the plastic number.
+const R2: (f64, f64) = (0.7548776662466927, 0.5698402909980532);
+// GOLDEN is the inverse of the golden ratio, the one dimensional R2 counterpart.
+const GOLDEN: f64 = 0.6180339887498949;
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+        let (x, y) = (x as f64, y as f64);
+        self.shift = (x * R2.0 + y * R2.1, x * R2.1 + y * R2.0);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.state.next(1);
+        fract(self.shift.0 + hashed_1d(h) + self.index(h) * GOLDEN)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.state.next(2);
+        let index = self.index(h);
+        (
+            fract(self.shift.0 + hashed_1d(h) + index * R2.0),
+            fract(self.shift.1 + hashed_1d(mix_bits(h)) + index * R2.1),
+        )
+    }
+}
+
+/// PixelSample tracks the current sample and dimension of hash based samplers.
+struct PixelSample {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dim: u32,
+}
+
+impl PixelSample {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dim: 0,
+        }
+    }
+
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = hash(self.seed, (x as u64) << 32 | y as u64);
+        self.index = index;
+        self.dim = 0;
+    }
+
+    /// next skips `n` dimensions and returns a hash identifying the first of them in this pixel.
+    fn next(&mut self, n: u32) -> u64 {
+        let h = hash(self.pixel, self.dim as u64);
+        self.dim += n;
+        h
+    }
+}
+
+// ONE_MINUS_EPSILON is the largest f64 below 1.
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+fn fract(x: f64) -> f64 {
+    (x - x.floor()).min(ONE_MINUS_EPSILON)
+}
+
+fn to_unit(v: u32) -> f64 {
+    v as f64 / (1u64 << 32) as f64
+}
+
+pub(crate) fn hashed_1d(h: u64) -> f64 {
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^ (v >> 33)
+}
+
+pub(crate) fn hash(a: u64, b: u64) -> u64 {
+    mix_bits(a ^ mix_bits(b.wrapping_add(0x9e37_79b9_7f4a_7c15)))
+}
+
+/// permutation_element returns the position of `i` in a random permutation of 0..n selected by `p`
+/// (Kensler, Correlated Multi-Jittered Sampling).
+fn permutation_element(mut i: u32, n: u32, p: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % n
+}
+
+/// nested_uniform_scramble Owen scrambles the binary digits of `v`, read from the most significant
+/// one, with Burley's hash based approximation.
+fn nested_uniform_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v = v.wrapping_add(seed);
+    v ^= v.wrapping_mul(0x6c50_b47c);
+    v ^= v.wrapping_mul(0xb82f_1e52);
+    v ^= v.wrapping_mul(0xc7af_e638);
+    v ^= v.wrapping_mul(0x8d22_f6e6);
+    v.reverse_bits()
+}
+
+// SOBOL_1 holds the direction numbers of the second Sobol dimension, the first one is the
+// bit reversed index.
+const SOBOL_1: [u32; 32] = sobol_directions();
+
+const fn sobol_directions() -> [u32; 32] {
+    let mut v = [0; 32];
+    let mut m: u32 = 1;
+    let mut k = 0;
+    while k < 32 {
+        v[k] = m << (31 - k);
+        m ^= m << 1;
+        k += 1;
+    }
+    v
+}
+
+fn sobol_2d(index: u32) -> (u32, u32) {
+    let mut y = 0;
+    let mut bits = index;
+    let mut k = 0;
+    while bits != 0 {
+        if bits & 1 != 0 {
+            y ^= SOBOL_1[k];
+        }
+        bits >>= 1;
+        k += 1;
+    }
+    (index.reverse_bits(), y)
+}
+
+const PRIMES: [u32; 128] = primes();
+
+const fn primes<const N: usize>() -> [u32; N] {
+    let mut primes = [0; N];
+    let mut n = 0;
+    let mut candidate = 2;
+    while n < N {
+        let mut i = 0;
+        let mut prime = true;
+        while i < n && primes[i] * primes[i] <= candidate {
+            if candidate % primes[i] == 0 {
+                prime = false;
+                break;
+            }
+            i += 1;
+        }
+        if prime {
+            primes[n] = candidate;
+            n += 1;
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+/// owen_scrambled_radical_inverse mirrors the digits of `a` in `base` around the radix point,
+/// permuting every digit depending on the digits before it.
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed = 0u64;
+    // Scrambled digits are not zero even after all digits of `a` are used,
+    // so continue until they no longer change the result.
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_hash = mix_bits(hash ^ reversed);
+        let digit = permutation_element(digit as u32, base as u32, digit_hash as u32);
+        reversed = reversed * base + digit as u64;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    (reversed as f64 * inv_base_m).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 5] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+        SamplerKind::BlueNoise,
+    ];
+
+    #[test]
+    fn sobol_sequence() {
+        let points: Vec<_> = (0..4)
+            .map(sobol_2d)
+            .map(|(x, y)| (to_unit(x), to_unit(y)))
+            .collect();
+        assert_eq!(points, [(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25)]);
+    }
+
+    #[test]
+    fn stratified_in_each_dimension() {
+        for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+            let mut sampler = kind.build(16, 7);
+            let mut cells = [[false; 16]; 2];
+            for index in 0..16 {
+                sampler.start_pixel_sample(3, 5, index);
+                sampler.get_2d();
+                let (x, y) = sampler.get_2d();
+                let z = sampler.get_1d();
+                for (cells, cell) in cells.iter_mut().zip([
+                    (x * 4.0) as usize * 4 + (y * 4.0) as usize,
+                    (z * 16.0) as usize,
+                ]) {
+                    assert!(!cells[cell], "{:?} sample {} shares a stratum", kind, index);
+                    cells[cell] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn low_discrepancy_converges_faster() {
+        // Integrate a smooth function of the third and fourth dimension over many pixels.
+        let f = |(x, y): (f64, f64)| (x * y * 3.0).sin();
+        let expected = 0.5187327;
+        let rmse = |kind: SamplerKind| {
+            let mut sampler = kind.build(64, 1);
+            let mut sum_squared = 0.0;
+            for pixel in 0..256 {
+                let mut sum = 0.0;
+                for index in 0..64 {
+                    sampler.start_pixel_sample(pixel % 16, pixel / 16, index);
+                    sampler.get_2d();
+                    let u = sampler.get_2d();
+                    assert!((0.0..1.0).contains(&u.0) && (0.0..1.0).contains(&u.1));
+                    sum += f(u);
+                }
+                sum_squared += (sum / 64.0 - expected).powi(2);
+            }
+            (sum_squared / 256.0).sqrt()
+        };
+
+        let independent = rmse(SamplerKind::Independent);
+        for kind in &KINDS[1..] {
+            let error = rmse(*kind);
+            assert!(
+                error < 0.5 * independent,
+                "{:?}: {} vs {}",
+                kind,
+                error,
+                independent
+            );
+        }
+    }
+}
@@ -0,0 +1,1105 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rand::SeedableRng;
+use serde::Deserialize;
+
+use crate::{
+    aarect::{XYRect, XZRect, YZRect},
+    box3d::Box3D,
+    bvh::{BVHError, BVHNode, LinearBVH},
+    camera::Camera,
+    color::Color,
+    constant_medium::ConstantMedium,
+    filter::FilterKind,
+    hittable::{Hittable, ObjectId},
+    hittable_list::HittableList,
+    light::{Light, LightList},
+    material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal},
+    moving_sphere::MovingSphere,
+    obj::{self, ObjError},
+    render::RenderSettings,
+    sampler::SamplerKind,
+    sphere::Sphere,
+    texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture},
+    transform::{Instance, Transform},
+    triangle::{Mesh, Triangle, TriangleMesh},
+    vec3::{Mat4, Vec3},
+};
+
+/// Scene is a world ready to be rendered together with its camera and render settings.
+pub struct Scene {
+    pub world: Arc<dyn Hittable>,
+    pub camera: CameraDescription,
+    pub background: Color,
+    pub settings: RenderSettings,
+    // lights are emissive objects which can be sampled directly.
+    pub lights: LightList,
+    // materials are the named materials in the order of their names, for material id AOVs.
+    pub materials: Vec<Arc<dyn Material>>,
+    // files are the models, material libraries and textures the scene was built from.
+    pub files: Vec<PathBuf>,
+}
+
+impl Scene {
+    /// camera builds the camera with the aspect ratio of the rendered image.
+    pub fn camera(&self) -> Camera {
+        self.camera
+            .build(self.settings.width as f64 / self.settings.height as f64)
+    }
+}
+
+/// load reads a TOML scene description, relative paths in it are resolved
+/// against the directory of the scene file.
+pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    SceneDescription::from_file(path)?.build(base_dir(path))
+}
+
+pub fn from_str(text: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let desc: SceneDescription = toml::from_str(text).map_err(SceneError::Parse)?;
+    desc.build(base_dir)
+}
+
+/// base_dir returns the directory relative paths in the scene file are resolved against.
+pub fn base_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, std::io::Error),
+    Parse(toml::de::Error),
+    Image(PathBuf),
+    UnknownTexture(String),
+    UnknownMaterial(String),
+    UnknownPrototype(String),
+    TextureCycle(String),
+    PrototypeCycle(String),
+    Obj(ObjError),
+    Bvh(BVHError),
+    Invalid(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "can't read {}: {}", path.display(), err),
+            Self::Parse(err) => write!(f, "invalid scene: {}", err),
+            Self::Image(path) => write!(f, "can't load image {}", path.display()),
+            Self::UnknownTexture(name) => write!(f, "unknown texture '{}'", name),
+            Self::UnknownMaterial(name) => write!(f, "unknown material '{}'", name),
+            Self::UnknownPrototype(name) => write!(f, "unknown prototype '{}'", name),
+            Self::TextureCycle(name) => write!(f, "texture '{}' refers to itself", name),
+            Self::PrototypeCycle(name) => write!(f, "prototype '{}' refers to itself", name),
+            Self::Obj(err) => write!(f, "can't load model: {}", err),
+            Self::Bvh(err) => write!(f, "invalid scene: {}", err),
+            Self::Invalid(msg) => write!(f, "invalid scene: {}", msg),
+        }
+    }
+}
+
+impl From<ObjError> for SceneError {
+    fn from(err: ObjError) -> Self {
+        Self::Obj(err)
+    }
+}
+
+impl From<BVHError> for SceneError {
+    fn from(err: BVHError) -> Self {
+        Self::Bvh(err)
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::Obj(err) => Some(err),
+            Self::Bvh(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// SceneDescription is the deserialized form of a scene file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub render: RenderDescription,
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub textures: BTreeMap<String, TextureDescription>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    // prototypes are objects built once and placed by instances.
+    #[serde(default)]
+    pub prototypes: BTreeMap<String, ObjectDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderDescription {
+    pub width: u32,
+    // height is derived from width and aspect_ratio if omitted.
+    pub height: Option<u32>,
+    pub aspect_ratio: f64,
+    pub samples_per_pixel: u32,
+    // adaptive_threshold enables adaptive sampling with samples_per_pixel as the maximum,
+    // pixels stop being sampled once their relative error is below the threshold.
+    pub adaptive_threshold: f64,
+    pub min_samples_per_pixel: u32,
+    pub max_depth: u32,
+    // rr_min_depth is the number of bounces after which Russian roulette may end paths.
+    pub rr_min_depth: u32,
+    pub background: [f64; 3],
+    // seed initializes random generators used to build (e.g. noise textures) and render the scene.
+    pub seed: u64,
+    // bvh wraps top level objects into a bounded volume hierarchy.
+    pub bvh: bool,
+    // bvh_builder selects how hierarchies are built, "sah" or "random".
+    pub bvh_builder: BvhBuilder,
+    // sampler is one of "independent", "stratified", "halton", "sobol" or "blue_noise".
+    pub sampler: SamplerKind,
+    // filter is one of "box", "tent", "gaussian", "mitchell" or "lanczos".
+    pub filter: FilterKind,
+    // filter_radius in pixels, each filter has its own default.
+    pub filter_radius: Option<f64>,
+    // denoise filters the finished image.
+    pub denoise: bool,
+}
+
+/// BvhBuilder chooses between a flattened SAH hierarchy (LinearBVH) and the random axis BVHNode::new.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BvhBuilder {
+    #[default]
+    Sah,
+    Random,
+}
+
+impl Default for RenderDescription {
+    fn default() -> Self {
+        Self {
+            width: 400,
+            height: None,
+            aspect_ratio: 16.0 / 9.0,
+            samples_per_pixel: 100,
+            adaptive_threshold: 0.0,
+            min_samples_per_pixel: 16,
+            max_depth: 50,
+            rr_min_depth: crate::render::RR_MIN_DEPTH,
+            background: [0.0; 3],
+            seed: 42,
+            bvh: true,
+            bvh_builder: BvhBuilder::Sah,
+            sampler: SamplerKind::default(),
+            filter: FilterKind::default(),
+            filter_radius: None,
+            denoise: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub look_from: [f64; 3],
+    pub look_at: [f64; 3],
+    #[serde(default = "CameraDescription::default_vup")]
+    pub vup: [f64; 3],
+    pub vfov: f64,
+    #[serde(default)]
+    pub aperture: f64,
+    #[serde(default = "CameraDescription::default_focus_dist")]
+    pub focus_dist: f64,
+    #[serde(default = "CameraDescription::default_time")]
+    pub time: [f64; 2],
+}
+
+impl CameraDescription {
+    fn default_vup() -> [f64; 3] {
+        [0.0, 1.0, 0.0]
+    }
+
+    fn default_focus_dist() -> f64 {
+        10.0
+    }
+
+    fn default_time() -> [f64; 2] {
+        [0.0, 1.0]
+    }
+
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        Camera::new(
+            vec3(self.look_from),
+            vec3(self.look_at),
+            vec3(self.vup),
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            self.focus_dist,
+            self.time[0]..self.time[1],
+        )
+    }
+}
+
+/// ColorOrTexture is either an inline RGB triple or a name of a texture.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ColorOrTexture {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        odd: ColorOrTexture,
+        even: ColorOrTexture,
+    },
+    Noise {
+        scale: f64,
+    },
+    Image {
+        path: PathBuf,
+        // data textures (e.g. normal maps) are not converted from sRGB.
+        #[serde(default)]
+        data: bool,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian { albedo: ColorOrTexture },
+    Metal { albedo: [f64; 3], fuzz: f64 },
+    Dielectric { ior: f64 },
+    DiffuseLight { emit: ColorOrTexture },
+    Isotropic { albedo: ColorOrTexture },
+}
+
+/// ObjectDescription is a shape with optional transforms applied in order.
+#[derive(Deserialize)]
+pub struct ObjectDescription {
+    #[serde(flatten)]
+    pub shape: ShapeDescription,
+    #[serde(default)]
+    pub transforms: Vec<TransformDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        #[serde(default = "CameraDescription::default_time")]
+        time: [f64; 2],
+        radius: f64,
+        material: String,
+    },
+    XyRect {
+        x: [f64; 2],
+        y: [f64; 2],
+        k: f64,
+        material: String,
+    },
+    XzRect {
+        x: [f64; 2],
+        z: [f64; 2],
+        k: f64,
+        material: String,
+    },
+    YzRect {
+        y: [f64; 2],
+        z: [f64; 2],
+        k: f64,
+        material: String,
+    },
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: String,
+    },
+    /// Mesh is an indexed triangle mesh, `normals` and `uvs` are per vertex.
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        #[serde(default)]
+        normals: Vec<[f64; 3]>,
+        #[serde(default)]
+        uvs: Vec<[f64; 2]>,
+        indices: Vec<[u32; 3]>,
+        material: String,
+    },
+    /// Obj is a Wavefront OBJ model, `material` is used for faces without one.
+    Obj {
+        path: PathBuf,
+        material: Option<String>,
+    },
+    ConstantMedium {
+        boundary: Box<ObjectDescription>,
+        density: f64,
+        albedo: ColorOrTexture,
+    },
+    /// List is a group of objects tested one by one.
+    List { objects: Vec<ObjectDescription> },
+    /// Bvh is a group of objects wrapped into a bounded volume hierarchy.
+    Bvh { objects: Vec<ObjectDescription> },
+    /// Instance places a prototype, usually with transforms. Instances share the prototype.
+    Instance { prototype: String },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformDescription {
+    Translate([f64; 3]),
+    // Rotations are in degrees.
+    RotateX(f64),
+    RotateY(f64),
+    RotateZ(f64),
+    Rotate { angle: f64, axis: [f64; 3] },
+    Scale([f64; 3]),
+    // Matrix is a row-major affine transform.
+    Matrix([[f64; 4]; 4]),
+}
+
+impl TransformDescription {
+    fn build(&self) -> Result<Transform, SceneError> {
+        Ok(match self {
+            Self::Translate(offset) => Transform::translate(vec3(*offset)),
+            Self::RotateX(angle) => Transform::rotate_x(*angle),
+            Self::RotateY(angle) => Transform::rotate_y(*angle),
+            Self::RotateZ(angle) => Transform::rotate_z(*angle),
+            Self::Rotate { angle, axis } => {
+                if *axis == [0.0; 3] {
+                    return Err(SceneError::Invalid("rotation axis must not be zero".into()));
+                }
+                Transform::rotate(*angle, vec3(*axis))
+            }
+            Self::Scale(factors) => {
+                if factors.contains(&0.0) {
+                    return Err(SceneError::Invalid("scale factors must not be zero".into()));
+                }
+                Transform::scale(vec3(*factors))
+            }
+            Self::Matrix(m) => {
+                if m[3] != [0.0, 0.0, 0.0, 1.0] {
+                    return Err(SceneError::Invalid(
+                        "last row of a transform matrix must be [0, 0, 0, 1]".into(),
+                    ));
+                }
+                Transform::from_matrix(Mat4::new(*m)).ok_or_else(|| {
+                    SceneError::Invalid("transform matrix can't be inverted".into())
+                })?
+            }
+        })
+    }
+}
+
+impl SceneDescription {
+    /// from_file reads a description which can be adjusted before it is built.
+    pub fn from_file(path: &Path) -> Result<Self, SceneError> {
+        let text = fs::read_to_string(path).map_err(|err| SceneError::Io(path.to_owned(), err))?;
+        toml::from_str(&text).map_err(SceneError::Parse)
+    }
+
+    pub fn build(self, base_dir: &Path) -> Result<Scene, SceneError> {
+        let render = &self.render;
+        if render.width == 0 || render.height == Some(0) {
+            return Err(SceneError::Invalid("image size must be positive".into()));
+        }
+        if !(render.aspect_ratio.is_finite() && render.aspect_ratio > 0.0) {
+            return Err(SceneError::Invalid("aspect ratio must be positive".into()));
+        }
+        if render
+            .filter_radius
+            .is_some_and(|r| !(r.is_finite() && r > 0.0))
+        {
+            return Err(SceneError::Invalid("filter radius must be positive".into()));
+        }
+        if render.samples_per_pixel == 0 {
+            return Err(SceneError::Invalid(
+                "samples per pixel must be positive".into(),
+            ));
+        }
+        if render.max_depth == 0 {
+            return Err(SceneError::Invalid("max depth must be positive".into()));
+        }
+
+        let camera = &self.camera;
+        if !(camera.vfov > 0.0 && camera.vfov < 180.0) {
+            return Err(SceneError::Invalid(
+                "field of view must be between 0 and 180 degrees".into(),
+            ));
+        }
+        if camera.look_from == camera.look_at {
+            return Err(SceneError::Invalid(
+                "camera position and the point it looks at must differ".into(),
+            ));
+        }
+        let view = vec3(camera.look_from) - vec3(camera.look_at);
+        if view.cross(vec3(camera.vup)).length_squared() == 0.0 {
+            return Err(SceneError::Invalid(
+                "camera up direction must not be parallel to the view direction".into(),
+            ));
+        }
+        if !(camera.aperture.is_finite() && camera.aperture >= 0.0) {
+            return Err(SceneError::Invalid("aperture must not be negative".into()));
+        }
+        if !(camera.focus_dist.is_finite() && camera.focus_dist > 0.0) {
+            return Err(SceneError::Invalid(
+                "focus distance must be positive".into(),
+            ));
+        }
+        let height = render
+            .height
+            .unwrap_or_else(|| ((render.width as f64 / render.aspect_ratio) as u32).max(1));
+        let settings = RenderSettings {
+            width: render.width,
+            height,
+            samples_per_pixel: render.samples_per_pixel,
+            adaptive_threshold: render.adaptive_threshold,
+            min_samples_per_pixel: render.min_samples_per_pixel,
+            max_depth: render.max_depth,
+            rr_min_depth: render.rr_min_depth,
+            seed: render.seed,
+            sampler: render.sampler,
+            filter: render.filter,
+            filter_radius: render.filter_radius,
+            denoise: render.denoise,
+            ..Default::default()
+        };
+
+        let mut builder = Builder {
+            rng: crate::Rng::seed_from_u64(render.seed),
+            base_dir,
+            bvh_builder: render.bvh_builder,
+            texture_desc: &self.textures,
+            textures: HashMap::new(),
+            prototype_desc: &self.prototypes,
+            prototypes: HashMap::new(),
+            materials: HashMap::new(),
+            emissive: HashSet::new(),
+            lights: Vec::new(),
+            unsampled: HashSet::new(),
+            files: Vec::new(),
+            media: 0,
+        };
+        let mut materials = Vec::with_capacity(self.materials.len());
+        for (name, desc) in self.materials.iter() {
+            let material = builder.material(desc)?;
+            materials.push(material.clone());
+            builder.materials.insert(name.clone(), material);
+            if let MaterialDescription::DiffuseLight { .. } = desc {
+                builder.emissive.insert(name.clone());
+            }
+        }
+
+        let time = self.camera.time[0]..self.camera.time[1];
+        let mut list = HittableList::default();
+        for (id, obj) in (1..).zip(self.objects.iter()) {
+            let object = builder.object(obj, time.start, time.end, true)?;
+            list.add(Arc::new(ObjectId::new(object, id)));
+        }
+
+        let world: Arc<dyn Hittable> = if render.bvh && !self.objects.is_empty() {
+            builder.bvh(list, time.start, time.end)?
+        } else {
+            Arc::new(list)
+        };
+
+        let mut lights = LightList::default();
+        for (material, light) in builder.lights {
+            if !builder.unsampled.contains(&material) {
+                lights.add(light);
+            }
+        }
+
+        Ok(Scene {
+            world,
+            camera: self.camera,
+            background: color(render.background),
+            settings: RenderSettings {
+                media: builder.media > 0,
+                ..settings
+            },
+            lights,
+            materials,
+            files: builder.files,
+        })
+    }
+}
+
+struct Builder<'a> {
+    rng: crate::Rng,
+    base_dir: &'a Path,
+    bvh_builder: BvhBuilder,
+    texture_desc: &'a BTreeMap<String, TextureDescription>,
+    // textures contains finished textures, `None` marks the ones being built.
+    textures: HashMap<String, Option<Arc<dyn Texture>>>,
+    prototype_desc: &'a BTreeMap<String, ObjectDescription>,
+    // prototypes contains finished prototypes, `None` marks the ones being built.
+    prototypes: HashMap<String, Option<Arc<dyn Hittable>>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    // emissive contains names of materials emitting light.
+    emissive: HashSet<String>,
+    lights: Vec<(String, Arc<dyn Light>)>,
+    // unsampled contains emissive materials used by objects which can't be sampled.
+    unsampled: HashSet<String>,
+    files: Vec<PathBuf>,
+    // media counts the media built so far, which seeds each of them.
+    media: u64,
+}
+
+impl<'a> Builder<'a> {
+    fn texture(&mut self, value: &ColorOrTexture) -> Result<Arc<dyn Texture>, SceneError> {
+        let name = match value {
+            ColorOrTexture::Color(c) => return Ok(Arc::new(SolidColor::new(color(*c)))),
+            ColorOrTexture::Texture(name) => name,
+        };
+        match self.textures.get(name) {
+            Some(Some(texture)) => return Ok(texture.clone()),
+            Some(None) => return Err(SceneError::TextureCycle(name.clone())),
+            None => {}
+        }
+
+        let desc = self
+            .texture_desc
+            .get(name)
+            .ok_or_else(|| SceneError::UnknownTexture(name.clone()))?;
+        self.textures.insert(name.clone(), None);
+        let texture: Arc<dyn Texture> = match desc {
+            TextureDescription::Solid { color: c } => Arc::new(SolidColor::new(color(*c))),
+            TextureDescription::Checker { odd, even } => Arc::new(
+                CheckerTexture::new_with_texture(self.texture(odd)?, self.texture(even)?),
+            ),
+            TextureDescription::Noise { scale } => {
+                Arc::new(NoiseTexture::new(&mut self.rng, *scale))
+            }
+            TextureDescription::Image { path, data } => {
+                let path = self.base_dir.join(path);
+                self.files.push(path.clone());
+                let filename = path.to_string_lossy();
+                let texture = if *data {
+                    ImageTexture::new_data(&filename)
+                } else {
+                    ImageTexture::new(&filename)
+                };
+                Arc::new(texture.ok_or(SceneError::Image(path))?)
+            }
+        };
+        self.textures.insert(name.clone(), Some(texture.clone()));
+        Ok(texture)
+    }
+
+    fn material(&mut self, desc: &MaterialDescription) -> Result<Arc<dyn Material>, SceneError> {
+        Ok(match desc {
+            MaterialDescription::Lambertian { albedo } => {
+                Arc::new(Lambertian::new_with_texture(self.texture(albedo)?))
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                if !(fuzz.is_finite() && *fuzz >= 0.0) {
+                    return Err(SceneError::Invalid(
+                        "metal fuzz must not be negative".into(),
+                    ));
+                }
+                Arc::new(Metal::new(color(*albedo), *fuzz))
+            }
+            MaterialDescription::Dielectric { ior } => {
+                if !(ior.is_finite() && *ior > 0.0) {
+                    return Err(SceneError::Invalid(
+                        "index of refraction must be positive".into(),
+                    ));
+                }
+                Arc::new(Dielectric::new(*ior))
+            }
+            MaterialDescription::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::new_with_texture(self.texture(emit)?))
+            }
+            MaterialDescription::Isotropic { albedo } => {
+                Arc::new(Isotropic::new_with_texture(self.texture(albedo)?))
+            }
+        })
+    }
+
+    fn bvh(
+        &mut self,
+        mut list: HittableList,
+        time0: f64,
+        time1: f64,
+    ) -> Result<Arc<dyn Hittable>, SceneError> {
+        match self.bvh_builder {
+            BvhBuilder::Sah => Ok(Arc::new(LinearBVH::from_hittable_list(list, time0, time1)?)),
+            BvhBuilder::Random => Ok(Arc::new(BVHNode::new(
+                &mut self.rng,
+                list.as_slice_mut(),
+                time0,
+                time1,
+            )?)),
+        }
+    }
+
+    fn named_material(&self, name: &str) -> Result<Arc<dyn Material>, SceneError> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SceneError::UnknownMaterial(name.to_owned()))
+    }
+
+    /// light registers an object with an emissive material for sampling.
+    /// Objects which can't be sampled make the whole material unsampled,
+    /// so that integrators still account for its emission when hit by chance.
+    fn light(&mut self, material: &str, light: Option<Arc<dyn Light>>) {
+        if !self.emissive.contains(material) {
+            return;
+        }
+        match light {
+            Some(light) => self.lights.push((material.to_owned(), light)),
+            None => {
+                self.unsampled.insert(material.to_owned());
+            }
+        }
+    }
+
+    /// object builds an object, `sampled` tells if emissive objects may be added to lights,
+    /// which isn't the case for transformed objects and medium boundaries.
+    fn object(
+        &mut self,
+        desc: &ObjectDescription,
+        time0: f64,
+        time1: f64,
+        sampled: bool,
+    ) -> Result<Arc<dyn Hittable>, SceneError> {
+        let sampled = sampled && desc.transforms.is_empty();
+        if let ShapeDescription::Sphere { radius, .. }
+        | ShapeDescription::MovingSphere { radius, .. } = &desc.shape
+        {
+            if !(radius.is_finite() && *radius > 0.0) {
+                return Err(SceneError::Invalid("sphere radius must be positive".into()));
+            }
+        }
+        if let ShapeDescription::Triangle { vertices, .. } = &desc.shape {
+            let [p0, p1, p2] = vertices.map(vec3);
+            let area = (p1 - p0).cross(p2 - p0).length();
+            if area == 0.0 || !area.is_finite() {
+                return Err(SceneError::Invalid(
+                    "triangle must have a positive area".into(),
+                ));
+            }
+        }
+        let mut object: Arc<dyn Hittable> = match &desc.shape {
+            ShapeDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                let sphere = Arc::new(Sphere::new(
+                    vec3(*center),
+                    *radius,
+                    self.named_material(material)?,
+                ));
+                self.light(material, sampled.then(|| sphere.clone() as Arc<dyn Light>));
+                sphere
+            }
+            ShapeDescription::MovingSphere {
+                center0,
+                center1,
+                time,
+                radius,
+                material,
+            } => {
+                self.light(material, None);
+                Arc::new(MovingSphere::new(
+                    vec3(*center0),
+                    vec3(*center1),
+                    time[0]..time[1],
+                    *radius,
+                    self.named_material(material)?,
+                ))
+            }
+            ShapeDescription::XyRect { x, y, k, material } => {
+                let rect = Arc::new(XYRect::new(
+                    x[0],
+                    x[1],
+                    y[0],
+                    y[1],
+                    *k,
+                    self.named_material(material)?,
+                ));
+                self.light(material, sampled.then(|| rect.clone() as Arc<dyn Light>));
+                rect
+            }
+            ShapeDescription::XzRect { x, z, k, material } => {
+                let rect = Arc::new(XZRect::new(
+                    x[0],
+                    x[1],
+                    z[0],
+                    z[1],
+                    *k,
+                    self.named_material(material)?,
+                ));
+                self.light(material, sampled.then(|| rect.clone() as Arc<dyn Light>));
+                rect
+            }
+            ShapeDescription::YzRect { y, z, k, material } => {
+                let rect = Arc::new(YZRect::new(
+                    y[0],
+                    y[1],
+                    z[0],
+                    z[1],
+                    *k,
+                    self.named_material(material)?,
+                ));
+                self.light(material, sampled.then(|| rect.clone() as Arc<dyn Light>));
+                rect
+            }
+            ShapeDescription::Box { min, max, material } => {
+                self.light(material, None);
+                Arc::new(Box3D::new(
+                    vec3(*min),
+                    vec3(*max),
+                    self.named_material(material)?,
+                ))
+            }
+            ShapeDescription::Triangle { vertices, material } => {
+                let triangle = Arc::new(Triangle::new(
+                    vec3(vertices[0]),
+                    vec3(vertices[1]),
+                    vec3(vertices[2]),
+                    self.named_material(material)?,
+                ));
+                self.light(
+                    material,
+                    sampled.then(|| triangle.clone() as Arc<dyn Light>),
+                );
+                triangle
+            }
+            ShapeDescription::Mesh {
+                positions,
+                normals,
+                uvs,
+                indices,
+                material,
+            } => {
+                self.light(material, None);
+                let mesh = Mesh {
+                    positions: positions.iter().copied().map(vec3).collect(),
+                    normals: normals.iter().copied().map(vec3).collect(),
+                    uvs: uvs.iter().map(|uv| (uv[0], uv[1])).collect(),
+                    indices: indices.clone(),
+                    material: self.named_material(material)?,
+                };
+                Arc::new(TriangleMesh::new(mesh).map_err(SceneError::Invalid)?)
+            }
+            ShapeDescription::Obj { path, material } => {
+                let material = match material {
+                    Some(name) => {
+                        self.light(name, None);
+                        self.named_material(name)?
+                    }
+                    None => Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73))),
+                };
+                let meshes =
+                    obj::load_with_files(&self.base_dir.join(path), material, &mut self.files)?;
+                self.bvh(meshes, time0, time1)?
+            }
+            ShapeDescription::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => {
+                let boundary = self.object(boundary, time0, time1, false)?;
+                self.media += 1;
+                Arc::new(
+                    ConstantMedium::new_with_texture(boundary, *density, self.texture(albedo)?)
+                        .with_seed(self.media),
+                )
+            }
+            ShapeDescription::List { objects } => {
+                let mut list = HittableList::default();
+                for obj in objects.iter() {
+                    list.add(self.object(obj, time0, time1, sampled)?);
+                }
+                Arc::new(list)
+            }
+            ShapeDescription::Bvh { objects } => {
+                let mut list = HittableList::default();
+                for obj in objects.iter() {
+                    list.add(self.object(obj, time0, time1, sampled)?);
+                }
+                self.bvh(list, time0, time1)?
+            }
+            ShapeDescription::Instance { prototype } => self.prototype(prototype, time0, time1)?,
+        };
+
+        // Transforms are combined into a single instance.
+        if !desc.transforms.is_empty() {
+            let mut transform = Transform::identity();
+            for t in desc.transforms.iter() {
+                transform = transform.then(&t.build()?);
+            }
+            object = Arc::new(Instance::new(object, transform, time0, time1));
+        }
+
+        Ok(object)
+    }
+
+    fn prototype(
+        &mut self,
+        name: &str,
+        time0: f64,
+        time1: f64,
+    ) -> Result<Arc<dyn Hittable>, SceneError> {
+        match self.prototypes.get(name) {
+            Some(Some(object)) => return Ok(object.clone()),
+            Some(None) => return Err(SceneError::PrototypeCycle(name.to_owned())),
+            None => {}
+        }
+
+        let desc = self
+            .prototype_desc
+            .get(name)
+            .ok_or_else(|| SceneError::UnknownPrototype(name.to_owned()))?;
+        self.prototypes.insert(name.to_owned(), None);
+        // Lights can't be sampled where instances place them.
+        let object = self.object(desc, time0, time1, false)?;
+        self.prototypes
+            .insert(name.to_owned(), Some(object.clone()));
+        Ok(object)
+    }
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+fn color(c: [f64; 3]) -> Color {
+    Color::new(c[0], c[1], c[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    #[test]
+    fn examples() {
+        for entry in fs::read_dir("scenes").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("toml".as_ref()) {
+                continue;
+            }
+            if let Err(err) = load(&path) {
+                panic!("{}: {}", path.display(), err);
+            }
+        }
+
+        // Referenced files are listed for checkpoint fingerprints.
+        let scene = load("scenes/obj_model.toml").unwrap();
+        let names: Vec<_> = scene.files.iter().filter_map(|f| f.file_name()).collect();
+        assert_eq!(names, ["shapes.obj", "shapes.mtl"]);
+        assert!(!scene.settings.media);
+
+        // Scenes with media have integrators draw samples for them.
+        assert!(load("scenes/cornell_smoke.toml").unwrap().settings.media);
+    }
+
+    #[test]
+    fn errors() {
+        let scene = r#"
+            [camera]
+            look_from = [0, 0, 0]
+            look_at = [0, 0, -1]
+            vfov = 90
+
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, -1]
+            radius = 0.5
+            material = "missing"
+        "#;
+        assert!(matches!(
+            from_str(scene, Path::new("")),
+            Err(SceneError::UnknownMaterial(name)) if name == "missing"
+        ));
+
+        let scene = r#"
+            [camera]
+            look_from = [0, 0, 0]
+            look_at = [0, 0, -1]
+            vfov = 90
+
+            [textures.a]
+            type = "checker"
+            odd = "b"
+            even = [1, 1, 1]
+
+            [textures.b]
+            type = "checker"
+            odd = "a"
+            even = [1, 1, 1]
+
+            [materials.m]
+            type = "lambertian"
+            albedo = "a"
+        "#;
+        assert!(matches!(
+            from_str(scene, Path::new("")),
+            Err(SceneError::TextureCycle(_))
+        ));
+
+        let scene = r#"
+            [camera]
+            look_from = [0, 0, 0]
+            look_at = [0, 0, -1]
+            vfov = 90
+
+            [prototypes.a]
+            type = "instance"
+            prototype = "a"
+
+            [[objects]]
+            type = "instance"
+            prototype = "a"
+        "#;
+        assert!(matches!(
+            from_str(scene, Path::new("")),
+            Err(SceneError::PrototypeCycle(_))
+        ));
+
+        let sphere = |material: &str| {
+            format!(
+                "[materials.m]\n{}\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, -1]\nradius = 1\nmaterial = \"m\"",
+                material
+            )
+        };
+        let render = |setting: &str| format!("[render]\n{}", setting);
+        let cases = [
+            ("", render("aspect_ratio = 0")),
+            ("", render("aspect_ratio = -1.5")),
+            ("", render("aspect_ratio = nan")),
+            ("", render("aspect_ratio = inf")),
+            ("", render("filter_radius = 0")),
+            ("", render("filter_radius = nan")),
+            ("", render("samples_per_pixel = 0")),
+            ("", render("max_depth = 0")),
+            ("vfov = 0", String::new()),
+            ("vfov = 180", String::new()),
+            ("look_at = [0, 0, 0]", String::new()),
+            ("vup = [0, 0, 2]", String::new()),
+            ("aperture = -1", String::new()),
+            ("focus_dist = 0", String::new()),
+            (
+                "",
+                sphere("type = \"lambertian\"\nalbedo = [1, 1, 1]")
+                    .replace("radius = 1", "radius = 0"),
+            ),
+            (
+                "",
+                sphere("type = \"lambertian\"\nalbedo = [1, 1, 1]")
+                    .replace("radius = 1", "radius = -1"),
+            ),
+            (
+                "",
+                sphere("type = \"metal\"\nalbedo = [1, 1, 1]\nfuzz = -0.5"),
+            ),
+            ("", sphere("type = \"dielectric\"\nior = 0")),
+            (
+                "",
+                sphere("type = \"lambertian\"\nalbedo = [1, 1, 1]")
+                    + "\ntransforms = [{ matrix = [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0.5, 0, 1]] }]",
+            ),
+            (
+                "",
+                sphere("type = \"lambertian\"\nalbedo = [1, 1, 1]").replace(
+                    "type = \"sphere\"\ncenter = [0, 0, -1]\nradius = 1",
+                    "type = \"triangle\"\nvertices = [[0, 0, -1], [1, 0, -1], [2, 0, -1]]",
+                ),
+            ),
+        ];
+        for (camera, rest) in cases {
+            let defaults = [
+                ("look_from", "[0, 0, 0]"),
+                ("look_at", "[0, 0, -1]"),
+                ("vfov", "90"),
+            ];
+            let mut lines: Vec<String> = defaults
+                .iter()
+                .filter(|(key, _)| !camera.starts_with(key))
+                .map(|(key, value)| format!("{} = {}", key, value))
+                .collect();
+            if !camera.is_empty() {
+                lines.push(camera.to_owned());
+            }
+            let scene = format!("[camera]\n{}\n\n{}\n", lines.join("\n"), rest);
+            assert!(
+                matches!(from_str(&scene, Path::new("")), Err(SceneError::Invalid(_))),
+                "{}",
+                scene
+            );
+        }
+    }
+
+    #[test]
+    fn instances() {
+        let scene = r#"
+            [camera]
+            look_from = [0, 0, 0]
+            look_at = [0, 0, -1]
+            vfov = 90
+
+            [render]
+            bvh = false
+
+            [materials.m]
+            type = "lambertian"
+            albedo = [0.5, 0.5, 0.5]
+
+            [prototypes.ball]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            material = "m"
+
+            [[objects]]
+            type = "instance"
+            prototype = "ball"
+            transforms = [{ scale = [1, 1, 0.5] }, { translate = [0, 0, -3] }]
+
+            [[objects]]
+            type = "instance"
+            prototype = "ball"
+            transforms = [{ rotate = { angle = 90, axis = [1, 0, 0] } }, { translate = [0, 5, -3] }]
+        "#;
+        let scene = from_str(scene, Path::new("")).unwrap();
+        let r = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-9);
+        assert_eq!(rec.object_id, 1);
+        let r = Ray::new(Vec3::default(), Vec3::new(0.0, 5.0, -3.0), 0.0);
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(rec.object_id, 2);
+    }
+}
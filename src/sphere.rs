@@ -1,21 +1,23 @@
 use std::f64::consts::PI;
 use std::ops::Neg;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::aabb::AABB;
 use crate::hittable::{HitRecord, Hittable};
+use crate::light::{area_to_solid_angle, Light, LightSample};
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vec3::{Point, Vec3};
+use crate::sampler::Sampler;
+use crate::vec3::{sample_unit_sphere, Onb, Point, Vec3};
 
 pub struct Sphere {
     center: Point,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point, radius: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Self {
         Self {
             center,
             radius,
@@ -31,7 +33,7 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = r.orig - self.center;
         let a = r.dir.length_squared();
         let half_b = oc.dot(r.dir);
@@ -62,7 +64,7 @@ impl Hittable for Sphere {
             r,
             u,
             v,
-            self.material.clone(),
+            self.material.as_ref(),
             outward_normal,
         ))
     }
@@ -74,3 +76,61 @@ impl Hittable for Sphere {
         ))
     }
 }
+
+impl Light for Sphere {
+    /// sample picks a direction in the cone subtended by the sphere,
+    /// or a point on the whole surface if `origin` is inside.
+    fn sample(&self, sampler: &mut dyn Sampler, origin: Point) -> Option<LightSample> {
+        let d = self.center - origin;
+        let dist_squared = d.length_squared();
+        let radius_squared = self.radius.powi(2);
+
+        let (p, wi, pdf) = if dist_squared <= radius_squared {
+            let n = sample_unit_sphere(sampler.get_2d());
+            let p = self.center + self.radius * n;
+            let area = 4.0 * PI * radius_squared;
+            let (wi, pdf) = area_to_solid_angle(area.recip(), origin, p, n)?;
+            (p, wi, pdf)
+        } else {
+            let cos_theta_max = (1.0 - radius_squared / dist_squared).sqrt();
+            let (u1, u2) = sampler.get_2d();
+            let z = 1.0 + u1 * (cos_theta_max - 1.0);
+            let phi = 2.0 * PI * u2;
+            let sin_theta = (1.0 - z * z).sqrt();
+            let wi = Onb::from_w(d)
+                .local(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
+                .normalize();
+            // Directions at the very edge of the cone may miss due to rounding.
+            let rec = self.hit(&Ray::new(origin, wi, 0.0), 0.0, f64::INFINITY)?;
+            (rec.p, wi, 1.0 / (2.0 * PI * (1.0 - cos_theta_max)))
+        };
+
+        let (u, v) = Self::get_uv(&((p - self.center) / self.radius));
+        Some(LightSample {
+            p,
+            wi,
+            pdf,
+            emitted: self.material.emitted(u, v, &p),
+        })
+    }
+
+    fn pdf_value(&self, origin: Point, dir: Vec3) -> f64 {
+        let Some(rec) = self.hit(&Ray::new(origin, dir, 0.0), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+
+        let dist_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius.powi(2);
+        if dist_squared <= radius_squared {
+            let area = 4.0 * PI * radius_squared;
+            let n = (rec.p - self.center) / self.radius;
+            return area_to_solid_angle(area.recip(), origin, rec.p, n).map_or(0.0, |(_, pdf)| pdf);
+        }
+        let cos_theta_max = (1.0 - radius_squared / dist_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+}
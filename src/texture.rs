@@ -1,10 +1,14 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use image::{ImageBuffer, Rgb};
+use image::{DynamicImage, ImageBuffer, Rgb};
 
-use crate::{color::Color, perlin::Perlin, vec3::Point};
+use crate::{
+    color::{srgb_to_linear, Color},
+    perlin::Perlin,
+    vec3::Point,
+};
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point) -> Color;
 }
 
@@ -29,19 +33,19 @@ impl Texture for SolidColor {
 }
 
 pub struct CheckerTexture {
-    odd: Rc<dyn Texture>,
-    even: Rc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    even: Arc<dyn Texture>,
 }
 
 impl CheckerTexture {
     pub fn new(c1: Color, c2: Color) -> Self {
         Self {
-            odd: Rc::new(SolidColor::new(c1)),
-            even: Rc::new(SolidColor::new(c2)),
+            odd: Arc::new(SolidColor::new(c1)),
+            even: Arc::new(SolidColor::new(c2)),
         }
     }
 
-    pub fn new_with_texture(odd: Rc<dyn Texture>, even: Rc<dyn Texture>) -> Self {
+    pub fn new_with_texture(odd: Arc<dyn Texture>, even: Arc<dyn Texture>) -> Self {
         Self { odd, even }
     }
 }
@@ -86,13 +90,35 @@ pub struct ImageTexture {
 }
 
 impl ImageTexture {
+    /// new loads a color texture, 8- and 16-bit images are treated as sRGB encoded
+    /// and are converted to linear values. Floating point images are already linear.
     pub fn new(filename: &str) -> Option<Self> {
-        image::io::Reader::open(filename)
+        Self::load(filename, true)
+    }
+
+    /// new_data loads a texture holding non-color data (e.g. normals or roughness)
+    /// which is used as is.
+    pub fn new_data(filename: &str) -> Option<Self> {
+        Self::load(filename, false)
+    }
+
+    fn load(filename: &str, linearize: bool) -> Option<Self> {
+        let img = image::io::Reader::open(filename)
             .ok()
-            .and_then(|f| f.decode().ok())
-            .map(|img| Self {
-                data: Some(img.to_rgb32f()),
-            })
+            .and_then(|f| f.decode().ok())?;
+        let is_float = matches!(
+            img,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+
+        let mut data = img.to_rgb32f();
+        if linearize && !is_float {
+            for px in data.pixels_mut() {
+                px.0 = px.0.map(|x| srgb_to_linear(x as f64) as f32);
+            }
+        }
+
+        Some(Self { data: Some(data) })
     }
 }
 
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+
+/// ToneMapper compresses linear HDR radiance into the displayable [0, 1] range.
+pub trait ToneMapper: Send + Sync {
+    fn map(&self, c: Color) -> Color;
+}
+
+/// Clamp cuts off everything above 1.
+pub struct Clamp;
+
+impl ToneMapper for Clamp {
+    fn map(&self, c: Color) -> Color {
+        c.clamp(0.0, 1.0)
+    }
+}
+
+/// Reinhard applies `L / (1 + L)` to the luminance.
+pub struct Reinhard;
+
+impl ToneMapper for Reinhard {
+    fn map(&self, c: Color) -> Color {
+        scale_luminance(c, |l| l / (1.0 + l))
+    }
+}
+
+/// ExtendedReinhard is Reinhard which maps luminance `white` and above to 1.
+pub struct ExtendedReinhard {
+    pub white: f64,
+}
+
+impl ExtendedReinhard {
+    pub fn new(white: f64) -> Self {
+        Self { white }
+    }
+}
+
+impl ToneMapper for ExtendedReinhard {
+    fn map(&self, c: Color) -> Color {
+        let w2 = self.white.powi(2);
+        scale_luminance(c, |l| l * (1.0 + l / w2) / (1.0 + l))
+    }
+}
+
+/// AcesFilmic is Krzysztof Narkowicz's fit of the ACES reference rendering transform.
+pub struct AcesFilmic;
+
+impl ToneMapper for AcesFilmic {
+    fn map(&self, c: Color) -> Color {
+        const A: f64 = 2.51;
+        const B: f64 = 0.03;
+        const C: f64 = 2.43;
+        const D: f64 = 0.59;
+        const E: f64 = 0.14;
+
+        c.map(|x| {
+            let x = x.max(0.0);
+            (x * (A * x + B)) / (x * (C * x + D) + E)
+        })
+        .clamp(0.0, 1.0)
+    }
+}
+
+/// Hable is John Hable's filmic curve from Uncharted 2.
+pub struct Hable {
+    pub white: f64,
+}
+
+impl Hable {
+    pub fn new(white: f64) -> Self {
+        Self { white }
+    }
+
+    fn partial(x: f64) -> f64 {
+        const A: f64 = 0.15;
+        const B: f64 = 0.50;
+        const C: f64 = 0.10;
+        const D: f64 = 0.20;
+        const E: f64 = 0.02;
+        const F: f64 = 0.30;
+
+        ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+    }
+}
+
+impl Default for Hable {
+    fn default() -> Self {
+        Self::new(11.2)
+    }
+}
+
+impl ToneMapper for Hable {
+    fn map(&self, c: Color) -> Color {
+        const EXPOSURE_BIAS: f64 = 2.0;
+
+        let white_scale = Self::partial(self.white).recip();
+        c.map(|x| Self::partial(EXPOSURE_BIAS * x.max(0.0)) * white_scale)
+            .clamp(0.0, 1.0)
+    }
+}
+
+fn scale_luminance(c: Color, f: impl Fn(f64) -> f64) -> Color {
+    let l = c.luminance();
+    if l <= 0.0 {
+        return Color::default();
+    }
+    (c * (f(l) / l)).clamp(0.0, 1.0)
+}
+
+/// by_name returns a tone mapper by its name as used on the command line and in scene files.
+pub fn by_name(name: &str) -> Option<Arc<dyn ToneMapper>> {
+    match name {
+        "clamp" => Some(Arc::new(Clamp)),
+        "reinhard" => Some(Arc::new(Reinhard)),
+        "extended-reinhard" => Some(Arc::new(ExtendedReinhard::new(4.0))),
+        "aces" => Some(Arc::new(AcesFilmic)),
+        "hable" => Some(Arc::new(Hable::default())),
+        _ => None,
+    }
+}
+
+/// ToneMapping turns linear radiance into sRGB encoded display values.
+#[derive(Clone)]
+pub struct ToneMapping {
+    // exposure is measured in stops, every stop doubles the radiance.
+    pub exposure: f64,
+    pub operator: Arc<dyn ToneMapper>,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            operator: Arc::new(Clamp),
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn new(exposure: f64, operator: Arc<dyn ToneMapper>) -> Self {
+        Self { exposure, operator }
+    }
+
+    pub fn apply(&self, c: Color) -> Color {
+        self.operator.map(c * self.exposure.exp2()).to_srgb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators() {
+        let gray = |x: f64| Color::new(x, x, x);
+        for name in ["clamp", "reinhard", "extended-reinhard", "aces", "hable"] {
+            let op = by_name(name).unwrap();
+            assert_eq!(op.map(Color::default()).luminance(), 0.0, "{}", name);
+
+            let mut last = 0.0;
+            for i in 1..=400 {
+                let c = op.map(gray(i as f64 * 0.05));
+                let l = c.luminance();
+                assert!(l >= last, "{} isn't monotonic at {}", name, i);
+                for x in [c.r(), c.g(), c.b()] {
+                    assert!((0.0..=1.0).contains(&x), "{} maps {} to {}", name, i, x);
+                }
+                last = l;
+            }
+
+            // Saturated colors stay in range too.
+            let c = op.map(Color::new(100.0, 0.5, 0.0));
+            for x in [c.r(), c.g(), c.b()] {
+                assert!((0.0..=1.0).contains(&x), "{} maps to {}", name, x);
+            }
+        }
+
+        assert!((Reinhard.map(gray(1.0)).r() - 0.5).abs() < 1e-12);
+        assert!((ExtendedReinhard::new(4.0).map(gray(4.0)).r() - 1.0).abs() < 1e-12);
+        assert!((Hable::new(4.0).map(gray(2.0)).r() - 1.0).abs() < 1e-12);
+        assert_eq!(Clamp.map(gray(0.3)).r(), 0.3);
+    }
+}
@@ -0,0 +1,321 @@
+use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    vec3::{Mat3, Mat4, Onb, Point, Quat, Vec3},
+};
+
+/// Transform is an affine transform kept together with its inverse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    m: Mat4,
+    inv: Mat4,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            m: Mat4::IDENTITY,
+            inv: Mat4::IDENTITY,
+        }
+    }
+
+    /// from_matrix returns the transform of a matrix, `None` if it can't be inverted.
+    pub fn from_matrix(m: Mat4) -> Option<Self> {
+        Some(Self {
+            m,
+            inv: m.inverse()?,
+        })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        Self {
+            m: Mat4::translation(offset),
+            inv: Mat4::translation(-offset),
+        }
+    }
+
+    /// scale scales along every axis, factors must not be zero.
+    pub fn scale(factors: Vec3) -> Self {
+        let recip = Vec3::new(factors.x.recip(), factors.y.recip(), factors.z.recip());
+        Self {
+            m: Mat4::from_mat3(Mat3::scale(factors), Vec3::default()),
+            inv: Mat4::from_mat3(Mat3::scale(recip), Vec3::default()),
+        }
+    }
+
+    /// rotate rotates counterclockwise by `angle` degrees looking down `axis` at the origin.
+    pub fn rotate(angle: f64, axis: Vec3) -> Self {
+        Self::from_rotation(Mat3::rotation(angle, axis))
+    }
+
+    /// from_quat rotates by a unit quaternion.
+    pub fn from_quat(q: Quat) -> Self {
+        Self::from_rotation(q.to_mat3())
+    }
+
+    fn from_rotation(r: Mat3) -> Self {
+        // Rotations are orthogonal, their inverse is the transpose.
+        Self {
+            m: Mat4::from_mat3(r, Vec3::default()),
+            inv: Mat4::from_mat3(r.transpose(), Vec3::default()),
+        }
+    }
+
+    pub fn rotate_x(angle: f64) -> Self {
+        Self::rotate(angle, Vec3::x(1.0))
+    }
+
+    pub fn rotate_y(angle: f64) -> Self {
+        Self::rotate(angle, Vec3::y(1.0))
+    }
+
+    pub fn rotate_z(angle: f64) -> Self {
+        Self::rotate(angle, Vec3::z(1.0))
+    }
+
+    /// look_at places the origin at `from` looking down -z towards `at` with y up,
+    /// as the camera does. `up` must not be parallel to the view direction.
+    pub fn look_at(from: Point, at: Point, up: Vec3) -> Self {
+        let basis = Onb::from_w_up(from - at, up);
+        let inv = Mat4::from_mat3(basis.to_mat3().transpose(), Vec3::default())
+            * Mat4::translation(-from);
+        Self {
+            m: Mat4::from_mat3(basis.to_mat3(), from),
+            inv,
+        }
+    }
+
+    /// then returns the transform applying `self` first and `next` after it.
+    pub fn then(&self, next: &Transform) -> Transform {
+        next * self
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Self {
+            m: self.inv,
+            inv: self.m,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        self.m
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        self.m.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.m.transform_vector(v)
+    }
+
+    /// normal transforms a normal by the inverse transpose, which keeps it perpendicular
+    /// to transformed surfaces under non-uniform scale. The result isn't normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inv.inverse_transform_normal(n)
+    }
+
+    /// ray transforms a ray without normalizing its direction, so hits keep their `t`.
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::new(self.point(r.orig), self.vector(r.dir), r.time).with_sample(r.sample)
+    }
+
+    /// bounding_box returns the box around the transformed corners of `b`.
+    pub fn bounding_box(&self, b: &AABB) -> AABB {
+        // Every axis of the result is the sum of the extremes of the matrix terms (Arvo).
+        let m = &self.m.m;
+        let mut min = Vec3::default();
+        let mut max = Vec3::default();
+        for i in 0..3 {
+            min[i] = m[i][3];
+            max[i] = m[i][3];
+            for (j, x) in m[i][..3].iter().enumerate() {
+                let (a, b) = (x * b.min[j], x * b.max[j]);
+                min[i] += a.min(b);
+                max[i] += a.max(b);
+            }
+        }
+        AABB::new(min, max)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// `a * b` applies `b` first, like the product of their matrices.
+impl Mul for &Transform {
+    type Output = Transform;
+
+    fn mul(self, other: &Transform) -> Transform {
+        Transform {
+            m: self.m * other.m,
+            inv: other.inv * self.inv,
+        }
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        &self * &other
+    }
+}
+
+/// Instance places an object with a transform. Many instances may share one object,
+/// and a single instance replaces any number of stacked `Translate` and `RotateY`.
+pub struct Instance {
+    pub object: Arc<dyn Hittable>,
+    // transform maps the object to world space.
+    pub transform: Transform,
+    bbox: Option<AABB>,
+}
+
+impl Instance {
+    /// new places `object`, its bounds cover the shutter interval from `time0` to `time1`.
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform, time0: f64, time1: f64) -> Self {
+        let bbox = object
+            .bounding_box(time0, time1)
+            .map(|b| transform.bounding_box(&b));
+        Self {
+            object,
+            transform,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        self.bbox.clone()
+    }
+
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let local = self.transform.inverse().ray(r);
+        self.object.hit(&local, t_min, t_max).map(|mut rec| {
+            rec.p = self.transform.point(rec.p);
+            // Transformed normals keep their side of the ray, front_face stays valid.
+            rec.normal = self.transform.normal(rec.normal).normalize();
+            rec
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        box3d::Box3D,
+        color::Color,
+        hittable::{RotateY, Translate},
+        material::Lambertian,
+        moving_sphere::MovingSphere,
+        sphere::Sphere,
+    };
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn composition_and_inverse() {
+        let t = Transform::scale(Vec3::new(2.0, 3.0, 0.5))
+            .then(&Transform::rotate(30.0, Vec3::new(1.0, 2.0, 3.0)))
+            .then(&Transform::translate(Vec3::new(1.0, -2.0, 4.0)));
+        let p = Vec3::new(0.3, -1.2, 2.0);
+        assert_near(t.inverse().point(t.point(p)), p);
+        let m = Transform::from_matrix(t.matrix()).unwrap();
+        assert_near(m.inverse().point(p), t.inverse().point(p));
+
+        assert_near(Transform::rotate_z(90.0).point(Vec3::x(1.0)), Vec3::y(1.0));
+        let look = Transform::look_at(Vec3::new(1.0, 2.0, 3.0), Vec3::default(), Vec3::y(1.0));
+        assert_near(look.point(Vec3::default()), Vec3::new(1.0, 2.0, 3.0));
+        assert_near(
+            look.vector(Vec3::z(-1.0)),
+            -Vec3::new(1.0, 2.0, 3.0).normalize(),
+        );
+        assert!(Transform::from_matrix(Mat4::new([[0.0; 4]; 4])).is_none());
+    }
+
+    #[test]
+    fn non_uniform_scale() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Arc::new(Sphere::new(Point::default(), 1.0, material));
+        let ellipsoid = Instance::new(sphere, Transform::scale(Vec3::new(2.0, 1.0, 1.0)), 0.0, 1.0);
+
+        let b = ellipsoid.bounding_box(0.0, 1.0).unwrap();
+        assert_near(b.min, Vec3::new(-2.0, -1.0, -1.0));
+        assert_near(b.max, Vec3::new(2.0, 1.0, 1.0));
+
+        // The normal of x²/4 + y² = 1 at a point is along (x / 4, y).
+        let r = Ray::new(Point::new(1.0, 5.0, 0.0), Vec3::y(-1.0), 0.0);
+        let rec = ellipsoid.hit(&r, 0.001, f64::INFINITY).unwrap();
+        let y = (1.0 - 0.25f64).sqrt();
+        assert_near(rec.p, Point::new(1.0, y, 0.0));
+        assert_near(rec.normal, Vec3::new(0.25, y, 0.0).normalize());
+        assert!((rec.t - (5.0 - y)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn matches_translate_and_rotate_y() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let b: Arc<dyn Hittable> = Arc::new(Box3D::new(
+            Point::default(),
+            Point::new(165.0, 330.0, 165.0),
+            material,
+        ));
+        let offset = Vec3::new(265.0, 0.0, 295.0);
+        let stacked = Translate::new(Arc::new(RotateY::new(b.clone(), 15.0)), offset);
+        let instance = Instance::new(
+            b,
+            Transform::rotate_y(15.0).then(&Transform::translate(offset)),
+            0.0,
+            1.0,
+        );
+
+        for (x, y) in [
+            (300.0, 100.0),
+            (350.0, 200.0),
+            (420.0, 50.0),
+            (320.0, 300.0),
+        ] {
+            let r = Ray::new(
+                Point::new(278.0, 278.0, -800.0),
+                Point::new(x, y, 400.0) - Point::new(278.0, 278.0, -800.0),
+                0.0,
+            );
+            let expected = stacked.hit(&r, 0.001, f64::INFINITY).unwrap();
+            let actual = instance.hit(&r, 0.001, f64::INFINITY).unwrap();
+            assert!((expected.t - actual.t).abs() < 1e-9);
+            assert_near(expected.p, actual.p);
+            assert_near(expected.normal, actual.normal);
+        }
+    }
+
+    #[test]
+    fn shutter() {
+        // The sphere keeps moving after time 1, up to x = 2 at the end of the shutter.
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Arc::new(MovingSphere::new(
+            Point::default(),
+            Point::x(1.0),
+            0.0..1.0,
+            0.5,
+            material,
+        ));
+        let instance = Instance::new(sphere, Transform::translate(Vec3::y(1.0)), 0.0, 2.0);
+
+        let b = instance.bounding_box(0.0, 2.0).unwrap();
+        assert_near(b.min, Vec3::new(-0.5, 0.5, -0.5));
+        assert_near(b.max, Vec3::new(2.5, 1.5, 0.5));
+        let r = Ray::new(Point::new(2.0, 1.0, 5.0), Vec3::z(-1.0), 2.0);
+        assert!(instance.hit(&r, 0.001, f64::INFINITY).is_some());
+    }
+}
@@ -0,0 +1,371 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    bvh::LinearBVH,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    light::{area_to_solid_angle, Light, LightSample},
+    material::Material,
+    ray::Ray,
+    sampler::Sampler,
+    vec3::{Point, Vec3},
+};
+
+/// Mesh holds vertex buffers shared by all triangles of a mesh.
+/// `normals` and `uvs` are either empty or have the same length as `positions`.
+pub struct Mesh {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub indices: Vec<[u32; 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Mesh {
+    /// validate checks that buffer sizes match and indices are in bounds.
+    pub fn validate(&self) -> Result<(), String> {
+        let n = self.positions.len();
+        if !self.normals.is_empty() && self.normals.len() != n {
+            return Err(format!(
+                "mesh has {} positions but {} normals",
+                n,
+                self.normals.len()
+            ));
+        }
+        if !self.uvs.is_empty() && self.uvs.len() != n {
+            return Err(format!(
+                "mesh has {} positions but {} texture coordinates",
+                n,
+                self.uvs.len()
+            ));
+        }
+        match self.indices.iter().flatten().find(|&&i| i as usize >= n) {
+            Some(i) => Err(format!("vertex index {} is out of bounds", i)),
+            None => Ok(()),
+        }
+    }
+
+    fn vertices(&self, face: usize) -> [Point; 3] {
+        self.indices[face].map(|i| self.positions[i as usize])
+    }
+}
+
+/// Triangle is a single face of a mesh.
+pub struct Triangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+}
+
+impl Triangle {
+    pub fn new(p0: Point, p1: Point, p2: Point, material: Arc<dyn Material>) -> Self {
+        let mesh = Mesh {
+            positions: vec![p0, p1, p2],
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: vec![[0, 1, 2]],
+            material,
+        };
+        Self {
+            mesh: Arc::new(mesh),
+            face: 0,
+        }
+    }
+
+    pub fn vertices(&self) -> [Point; 3] {
+        self.mesh.vertices(self.face)
+    }
+
+    /// intersect returns t and barycentric coordinates of the hit point.
+    /// It implements "Watertight Ray/Triangle Intersection" by Woop, Benthin and Wald,
+    /// so rays never slip through edges shared by adjacent triangles.
+    fn intersect(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, [f64; 3])> {
+        // Translate vertices to the ray origin.
+        let p = self.vertices().map(|p| p - r.orig);
+
+        // Permute components so that z is the dominant direction axis.
+        let kz = max_dimension(r.dir);
+        let kx = (kz + 1) % 3;
+        let ky = (kx + 1) % 3;
+        let d = Vec3::new(r.dir[kx], r.dir[ky], r.dir[kz]);
+        let mut p = p.map(|p| Vec3::new(p[kx], p[ky], p[kz]));
+
+        // Shear so that the ray direction becomes +z.
+        let sx = -d.x / d.z;
+        let sy = -d.y / d.z;
+        let sz = d.z.recip();
+        for p in p.iter_mut() {
+            p.x += sx * p.z;
+            p.y += sy * p.z;
+        }
+
+        let e0 = p[1].x * p[2].y - p[1].y * p[2].x;
+        let e1 = p[2].x * p[0].y - p[2].y * p[0].x;
+        let e2 = p[0].x * p[1].y - p[0].y * p[1].x;
+        if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+            return None;
+        }
+        let det = e0 + e1 + e2;
+        if det == 0.0 {
+            return None;
+        }
+
+        // Compare scaled distance to avoid division for misses.
+        let t_scaled = (e0 * p[0].z + e1 * p[1].z + e2 * p[2].z) * sz;
+        if det < 0.0 && (t_scaled >= t_min * det || t_scaled < t_max * det) {
+            return None;
+        }
+        if det > 0.0 && (t_scaled <= t_min * det || t_scaled > t_max * det) {
+            return None;
+        }
+
+        let inv_det = det.recip();
+        Some((
+            t_scaled * inv_det,
+            [e0 * inv_det, e1 * inv_det, e2 * inv_det],
+        ))
+    }
+}
+
+impl Triangle {
+    /// uv interpolates texture coordinates of barycentric coordinates `b`.
+    fn uv(&self, b: [f64; 3]) -> (f64, f64) {
+        if self.mesh.uvs.is_empty() {
+            return (b[1] + b[2], b[2]);
+        }
+        let uv = self.mesh.indices[self.face].map(|i| self.mesh.uvs[i as usize]);
+        (
+            b[0] * uv[0].0 + b[1] * uv[1].0 + b[2] * uv[2].0,
+            b[0] * uv[0].1 + b[1] * uv[1].1 + b[2] * uv[2].1,
+        )
+    }
+
+    fn area_and_normal(&self) -> (f64, Vec3) {
+        let [p0, p1, p2] = self.vertices();
+        let n = (p1 - p0).cross(p2 - p0);
+        let length = n.length();
+        (0.5 * length, n / length)
+    }
+}
+
+fn max_dimension(v: Vec3) -> usize {
+    let (x, y, z) = (v.x.abs(), v.y.abs(), v.z.abs());
+    if x > y && x > z {
+        0
+    } else if y > z {
+        1
+    } else {
+        2
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, b) = self.intersect(r, t_min, t_max)?;
+        let [p0, p1, p2] = self.vertices();
+        let idx = self.mesh.indices[self.face].map(|i| i as usize);
+
+        let p = b[0] * p0 + b[1] * p1 + b[2] * p2;
+        let (u, v) = self.uv(b);
+
+        let geometric_normal = (p1 - p0).cross(p2 - p0).normalize();
+        let mut rec = HitRecord::new(t, p, r, u, v, self.mesh.material.as_ref(), geometric_normal);
+
+        if !self.mesh.normals.is_empty() {
+            let n = idx.map(|i| self.mesh.normals[i]);
+            let shading_normal = b[0] * n[0] + b[1] * n[1] + b[2] * n[2];
+            if !shading_normal.near_zero() {
+                // Keep the shading normal on the same side as the face normal.
+                let shading_normal = shading_normal.normalize();
+                rec.normal = if shading_normal.dot(rec.normal) < 0.0 {
+                    -shading_normal
+                } else {
+                    shading_normal
+                };
+            }
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        let [p0, p1, p2] = self.vertices();
+        let mut min = Point::new(
+            p0.x.min(p1.x).min(p2.x),
+            p0.y.min(p1.y).min(p2.y),
+            p0.z.min(p1.z).min(p2.z),
+        );
+        let mut max = Point::new(
+            p0.x.max(p1.x).max(p2.x),
+            p0.y.max(p1.y).max(p2.y),
+            p0.z.max(p1.z).max(p2.z),
+        );
+
+        // Axis-aligned triangles would have flat boxes.
+        for a in 0..3 {
+            if max[a] - min[a] < 0.0002 {
+                min[a] -= 0.0001;
+                max[a] += 0.0001;
+            }
+        }
+        Some(AABB::new(min, max))
+    }
+}
+
+impl Light for Triangle {
+    fn sample(&self, sampler: &mut dyn Sampler, origin: Point) -> Option<LightSample> {
+        // Uniform sampling of barycentric coordinates.
+        let (u1, u2) = sampler.get_2d();
+        let su = u1.sqrt();
+        let b1 = u2 * su;
+        let b = [1.0 - su, b1, su - b1];
+        let [p0, p1, p2] = self.vertices();
+        let p = b[0] * p0 + b[1] * p1 + b[2] * p2;
+
+        let (area, n) = self.area_and_normal();
+        let (wi, pdf) = area_to_solid_angle(area.recip(), origin, p, n)?;
+        let (u, v) = self.uv(b);
+        Some(LightSample {
+            p,
+            wi,
+            pdf,
+            emitted: self.mesh.material.emitted(u, v, &p),
+        })
+    }
+
+    fn pdf_value(&self, origin: Point, dir: Vec3) -> f64 {
+        let Some((t, _)) = self.intersect(&Ray::new(origin, dir, 0.0), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let (area, n) = self.area_and_normal();
+        let p = origin + t * dir;
+        area_to_solid_angle(area.recip(), origin, p, n).map_or(0.0, |(_, pdf)| pdf)
+    }
+
+    fn material(&self) -> &dyn Material {
+        self.mesh.material.as_ref()
+    }
+}
+
+/// TriangleMesh is an indexed triangle mesh with its own bounded volume hierarchy.
+pub struct TriangleMesh {
+    mesh: Arc<Mesh>,
+    bvh: Arc<dyn Hittable>,
+}
+
+impl TriangleMesh {
+    /// new fails if the mesh has no faces or its buffers are inconsistent.
+    pub fn new(mesh: Mesh) -> Result<Self, String> {
+        if mesh.indices.is_empty() {
+            return Err("mesh has no faces".into());
+        }
+        mesh.validate()?;
+
+        let mesh = Arc::new(mesh);
+        let mut triangles = HittableList::default();
+        for face in 0..mesh.indices.len() {
+            triangles.add(Arc::new(Triangle {
+                mesh: mesh.clone(),
+                face,
+            }));
+        }
+
+        let bvh: Arc<dyn Hittable> = if mesh.indices.len() == 1 {
+            Arc::new(triangles)
+        } else {
+            Arc::new(LinearBVH::from_hittable_list(triangles, 0.0, 0.0).map_err(|e| e.to_string())?)
+        };
+        Ok(Self { mesh, bvh })
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.bvh.bounding_box(time0, time1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn hit() {
+        let tri = Triangle::new(
+            Point::new(-1.0, -1.0, -2.0),
+            Point::new(1.0, -1.0, -2.0),
+            Point::new(0.0, 1.0, -2.0),
+            material(),
+        );
+
+        let rec = tri
+            .hit(
+                &Ray::new(Point::default(), Vec3::z(-1.0), 0.0),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!((rec.normal - Vec3::z(1.0)).near_zero());
+        assert!(rec.front_face);
+
+        // Behind the origin and outside of the triangle.
+        assert!(tri
+            .hit(
+                &Ray::new(Point::default(), Vec3::z(1.0), 0.0),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+        assert!(tri
+            .hit(
+                &Ray::new(Point::x(2.0), Vec3::z(-1.0), 0.0),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+        assert!(tri
+            .hit(&Ray::new(Point::default(), Vec3::z(-1.0), 0.0), 0.001, 1.0)
+            .is_none());
+    }
+
+    #[test]
+    fn shared_edge() {
+        let mesh = TriangleMesh::new(Mesh {
+            positions: vec![
+                Point::new(0.0, 0.0, 0.0),
+                Point::new(1.0, 0.0, 0.0),
+                Point::new(1.0, 1.0, 0.0),
+                Point::new(0.0, 1.0, 0.0),
+            ],
+            normals: vec![Vec3::z(1.0); 4],
+            uvs: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            material: material(),
+        })
+        .unwrap();
+
+        // Rays through the diagonal shared by both triangles must not slip through.
+        for i in 1..100 {
+            let x = i as f64 / 100.0;
+            let r = Ray::new(Point::new(x, x, 1.0), Vec3::new(0.001, -0.002, -1.0), 0.0);
+            let rec = mesh.hit(&r, 0.001, f64::INFINITY).unwrap();
+            assert!((rec.u - rec.p.x).abs() < 1e-9);
+            assert!((rec.v - rec.p.y).abs() < 1e-9);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::fmt;
-use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub};
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 
 use rand::Rng;
 
@@ -93,6 +93,53 @@ pub fn random_in_unit_sphere(rng: &mut crate::Rng) -> Vec3 {
     }
 }
 
+pub fn random_unit_vector(rng: &mut crate::Rng) -> Vec3 {
+    loop {
+        let p = random_in_unit_sphere(rng);
+        if !p.near_zero() {
+            return p.normalize();
+        }
+    }
+}
+
+/// random_cosine_direction returns a unit vector around +z with density `cos(theta) / pi`.
+pub fn random_cosine_direction(rng: &mut crate::Rng) -> Vec3 {
+    sample_cosine_direction((rng.gen(), rng.gen()))
+}
+
+/// sample_cosine_direction maps a point of the unit square to a unit vector around +z
+/// with density `cos(theta) / pi`.
+pub fn sample_cosine_direction((u1, u2): (f64, f64)) -> Vec3 {
+    let phi = 2.0 * std::f64::consts::PI * u1;
+    let r = u2.sqrt();
+    Vec3::new(phi.cos() * r, phi.sin() * r, (1.0 - u2).sqrt())
+}
+
+/// sample_unit_sphere maps a point of the unit square uniformly onto the unit sphere.
+pub fn sample_unit_sphere((u1, u2): (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u1;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u2;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// sample_unit_disc maps a point of the unit square to the unit disc in the xy plane
+/// with the area preserving concentric mapping.
+pub fn sample_unit_disc((u1, u2): (f64, f64)) -> Vec3 {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    let (x, y) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vec3::default();
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
 pub fn random_in_hemisphere(rng: &mut crate::Rng, normal: Vec3) -> Vec3 {
     let p = random_in_unit_sphere(rng);
     if p.dot(normal) > 0.0 {
@@ -114,6 +161,399 @@ pub fn random_in_unit_disc(rng: &mut crate::Rng) -> Vec3 {
     }
 }
 
+/// Onb is an orthonormal basis built around `w`.
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(u: Vec3, v: Vec3, w: Vec3) -> Self {
+        Self { u, v, w }
+    }
+
+    /// from_w_up builds the basis with `w` along `w` and `v` as close to `up` as possible,
+    /// as cameras do. `up` must not be parallel to `w`.
+    pub fn from_w_up(w: Vec3, up: Vec3) -> Self {
+        let w = w.normalize();
+        let u = up.cross(w).normalize();
+        let v = w.cross(u);
+        Self { u, v, w }
+    }
+
+    pub fn from_w(n: Vec3) -> Self {
+        let w = n.normalize();
+        let a = if w.x.abs() > 0.9 {
+            Vec3::y(1.0)
+        } else {
+            Vec3::x(1.0)
+        };
+        let v = w.cross(a).normalize();
+        let u = w.cross(v);
+        Self { u, v, w }
+    }
+
+    /// local converts coordinates in the basis into world ones.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+
+    /// to_local converts world coordinates into ones in the basis, the inverse of `local`.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+
+    /// to_mat3 returns the matrix of `local`, with u, v and w as columns.
+    pub fn to_mat3(&self) -> Mat3 {
+        Mat3::from_cols(self.u, self.v, self.w)
+    }
+}
+
+/// Mat3 is a row-major 3x3 matrix acting on column vectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat3 {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3 {
+        m: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub fn new(m: [[f64; 3]; 3]) -> Self {
+        Self { m }
+    }
+
+    pub fn from_cols(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        Self::new([[a.x, b.x, c.x], [a.y, b.y, c.y], [a.z, b.z, c.z]])
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x, 0.0, 0.0],
+            [0.0, factors.y, 0.0],
+            [0.0, 0.0, factors.z],
+        ])
+    }
+
+    /// rotation rotates counterclockwise by `angle` degrees looking down `axis` at the origin.
+    pub fn rotation(angle: f64, axis: Vec3) -> Self {
+        let a = axis.normalize();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        // Rodrigues' rotation formula.
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = a[i] * a[j] * (1.0 - cos) + if i == j { cos } else { 0.0 };
+            }
+        }
+        m[0][1] -= a.z * sin;
+        m[0][2] += a.y * sin;
+        m[1][0] += a.z * sin;
+        m[1][2] -= a.x * sin;
+        m[2][0] -= a.y * sin;
+        m[2][1] += a.x * sin;
+        Self::new(m)
+    }
+
+    pub fn row(&self, i: usize) -> Vec3 {
+        Vec3::new(self.m[i][0], self.m[i][1], self.m[i][2])
+    }
+
+    pub fn col(&self, j: usize) -> Vec3 {
+        Vec3::new(self.m[0][j], self.m[1][j], self.m[2][j])
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_cols(self.row(0), self.row(1), self.row(2))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.row(0).dot(self.row(1).cross(self.row(2)))
+    }
+
+    /// inverse returns `None` for singular matrices.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        // The columns of the inverse are the cross products of the rows over the determinant.
+        let (r0, r1, r2) = (self.row(0), self.row(1), self.row(2));
+        Some(Self::from_cols(
+            r1.cross(r2) / det,
+            r2.cross(r0) / det,
+            r0.cross(r1) / det,
+        ))
+    }
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, other: Mat3) -> Mat3 {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.row(i).dot(other.col(j));
+            }
+        }
+        Mat3::new(m)
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.row(0).dot(v), self.row(1).dot(v), self.row(2).dot(v))
+    }
+}
+
+/// Mat4 is a row-major 4x4 matrix acting on column vectors of homogeneous coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    /// from_mat3 returns the affine matrix applying `linear` and then translating.
+    pub fn from_mat3(linear: Mat3, translation: Vec3) -> Self {
+        let mut m = Self::IDENTITY.m;
+        for i in 0..3 {
+            m[i][..3].copy_from_slice(&linear.m[i]);
+            m[i][3] = translation[i];
+        }
+        Self::new(m)
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::from_mat3(Mat3::IDENTITY, offset)
+    }
+
+    /// linear returns the upper left 3x3 part.
+    pub fn linear(&self) -> Mat3 {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            row.copy_from_slice(&self.m[i][..3]);
+        }
+        Mat3::new(m)
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.m[j][i];
+            }
+        }
+        Self::new(t)
+    }
+
+    /// inverse uses Gauss-Jordan elimination with partial pivoting,
+    /// it returns `None` for singular matrices.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let p = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= p;
+                inv[col][j] /= p;
+            }
+            for i in 0..4 {
+                if i != col {
+                    let f = a[i][col];
+                    for j in 0..4 {
+                        a[i][j] -= f * a[col][j];
+                        inv[i][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    /// transform_point applies the matrix to a point, dividing by w for projective matrices.
+    pub fn transform_point(&self, p: Point) -> Point {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * p.x + m[i][1] * p.y + m[i][2] * p.z + m[i][3];
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x, y, z) / w
+        }
+    }
+
+    /// transform_vector applies the matrix to a direction, which translations don't move.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.linear() * v
+    }
+
+    /// transform_normal transforms a normal of a surface transformed by the matrix. Normals
+    /// take the inverse transpose to stay perpendicular under non-uniform scale, the result
+    /// isn't normalized. It returns `None` for singular matrices, which flatten surfaces.
+    pub fn transform_normal(&self, n: Vec3) -> Option<Vec3> {
+        let inv = Self::from_mat3(self.linear().inverse()?, Vec3::default());
+        Some(inv.inverse_transform_normal(n))
+    }
+
+    /// inverse_transform_normal is transform_normal by the inverse of the matrix, for callers
+    /// which already have the inverse of the matrix that moves the surface.
+    pub fn inverse_transform_normal(&self, n: Vec3) -> Vec3 {
+        self.linear().transpose() * n
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+/// Quat is a quaternion, unit quaternions represent rotations.
+#[derive(Clone, Copy, Debug)]
+pub struct Quat {
+    pub v: Vec3,
+    pub w: f64,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        v: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        w: 1.0,
+    };
+
+    pub fn new(v: Vec3, w: f64) -> Self {
+        Self { v, w }
+    }
+
+    /// rotation rotates counterclockwise by `angle` degrees looking down `axis` at the origin,
+    /// like `Mat3::rotation`.
+    pub fn rotation(angle: f64, axis: Vec3) -> Self {
+        let (sin, cos) = (angle.to_radians() / 2.0).sin_cos();
+        Self::new(axis.normalize() * sin, cos)
+    }
+
+    pub fn dot(&self, other: Quat) -> f64 {
+        self.v.dot(other.v) + self.w * other.w
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        Self::new(self.v / length, self.w / length)
+    }
+
+    /// conjugate is the inverse rotation of a unit quaternion.
+    pub fn conjugate(&self) -> Self {
+        Self::new(-self.v, self.w)
+    }
+
+    /// rotate rotates `v` by the unit quaternion.
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        // v + 2w (q x v) + 2 q x (q x v), with q the vector part.
+        let t = 2.0 * self.v.cross(v);
+        v + self.w * t + self.v.cross(t)
+    }
+
+    pub fn to_mat3(&self) -> Mat3 {
+        Mat3::from_cols(
+            self.rotate(Vec3::x(1.0)),
+            self.rotate(Vec3::y(1.0)),
+            self.rotate(Vec3::z(1.0)),
+        )
+    }
+
+    /// slerp interpolates between unit quaternions at constant angular speed,
+    /// along the shorter way.
+    pub fn slerp(&self, other: Quat, t: f64) -> Quat {
+        let mut cos = self.dot(other);
+        let mut other = other;
+        if cos < 0.0 {
+            // q and -q are the same rotation.
+            cos = -cos;
+            other = Quat::new(-other.v, -other.w);
+        }
+        let (a, b) = if cos > 0.9995 {
+            // Nearly equal rotations are interpolated linearly to avoid dividing by ~0.
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        Quat::new(self.v * a + other.v * b, self.w * a + other.w * b).normalize()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// `a * b` rotates by `b` first, like the product of their matrices.
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, other: Quat) -> Quat {
+        Quat::new(
+            self.w * other.v + other.w * self.v + self.v.cross(other.v),
+            self.w * other.w - self.v.dot(other.v),
+        )
+    }
+}
+
 impl fmt::Display for Vec3 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} {} {}", self.x, self.y, self.z)
@@ -261,9 +701,36 @@ impl Index<usize> for Vec3 {
     }
 }
 
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, i: usize) -> &mut f64 {
+        match i {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => unreachable!(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Vec3;
+    use super::{Mat3, Mat4, Onb, Quat, Vec3};
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    fn assert_mat3_near(a: Mat3, b: Mat3) {
+        for i in 0..3 {
+            assert_near(a.row(i), b.row(i));
+        }
+    }
+
+    fn assert_mat4_near(a: Mat4, b: Mat4) {
+        for (x, y) in a.m.iter().flatten().zip(b.m.iter().flatten()) {
+            assert!((x - y).abs() < 1e-9, "{:?} != {:?}", a, b);
+        }
+    }
 
     #[test]
     fn operations() {
@@ -307,4 +774,165 @@ mod tests {
         assert!((n.z - 0.577350269).abs() < EPSILON);
         assert!((n.length() - 1.0).abs() < EPSILON);
     }
+
+    #[test]
+    fn mat3() {
+        let m = Mat3::new([[2.0, 0.0, 1.0], [1.0, 3.0, 2.0], [1.0, 1.0, 2.0]]);
+        assert!((m.determinant() - 6.0).abs() < 1e-12);
+        assert_mat3_near(m * m.inverse().unwrap(), Mat3::IDENTITY);
+        assert_mat3_near(m.inverse().unwrap() * m, Mat3::IDENTITY);
+        assert_eq!(m.transpose().transpose(), m);
+        assert_near(m.transpose().row(0), m.col(0));
+        assert_near(m * Vec3::new(1.0, 2.0, 3.0), Vec3::new(5.0, 13.0, 9.0));
+        assert!(
+            Mat3::new([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]])
+                .inverse()
+                .is_none()
+        );
+
+        // Rotations are counterclockwise looking down the axis, orthogonal and keep handedness.
+        let r = Mat3::rotation(90.0, Vec3::z(1.0));
+        assert_near(r * Vec3::x(1.0), Vec3::y(1.0));
+        assert_near(
+            Mat3::rotation(90.0, Vec3::x(1.0)) * Vec3::y(1.0),
+            Vec3::z(1.0),
+        );
+        assert_near(
+            Mat3::rotation(90.0, Vec3::y(1.0)) * Vec3::z(1.0),
+            Vec3::x(1.0),
+        );
+        let r = Mat3::rotation(37.0, Vec3::new(1.0, -2.0, 0.5));
+        assert_mat3_near(r * r.transpose(), Mat3::IDENTITY);
+        assert!((r.determinant() - 1.0).abs() < 1e-12);
+        assert_near(r * Vec3::new(1.0, -2.0, 0.5), Vec3::new(1.0, -2.0, 0.5));
+    }
+
+    #[test]
+    fn mat4() {
+        let linear =
+            Mat3::rotation(30.0, Vec3::new(1.0, 1.0, 0.0)) * Mat3::scale(Vec3::new(2.0, 3.0, 0.5));
+        let m = Mat4::from_mat3(linear, Vec3::new(1.0, -2.0, 4.0));
+        let inv = m.inverse().unwrap();
+        assert_mat4_near(m * inv, Mat4::IDENTITY);
+        assert_mat4_near(inv * m, Mat4::IDENTITY);
+        assert_mat4_near(m.transpose().transpose(), m);
+        assert_mat3_near(m.linear(), linear);
+        assert!(Mat4::new([[0.0; 4]; 4]).inverse().is_none());
+
+        // Products apply the right matrix first.
+        let t = Mat4::translation(Vec3::x(1.0));
+        let s = Mat4::from_mat3(Mat3::scale(Vec3::new_eq(2.0)), Vec3::default());
+        assert_near((t * s).transform_point(Vec3::x(1.0)), Vec3::x(3.0));
+        assert_near((s * t).transform_point(Vec3::x(1.0)), Vec3::x(4.0));
+
+        // Projective matrices divide by w.
+        let mut p = Mat4::IDENTITY;
+        p.m[3] = [0.0, 0.0, 1.0, 0.0];
+        assert_near(
+            p.transform_point(Vec3::new(2.0, 4.0, 2.0)),
+            Vec3::new(1.0, 2.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn points_vectors_and_normals() {
+        let m = Mat4::from_mat3(
+            Mat3::scale(Vec3::new(2.0, 1.0, 1.0)),
+            Vec3::new(5.0, 0.0, 0.0),
+        );
+
+        // Points are translated, vectors aren't.
+        assert_near(
+            m.transform_point(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(7.0, 1.0, 1.0),
+        );
+        assert_near(
+            m.transform_vector(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(2.0, 1.0, 1.0),
+        );
+
+        // The plane x + y = 0 becomes x / 2 + y = 0, with normal along (1 / 2, 1).
+        let n = m.transform_normal(Vec3::new(1.0, 1.0, 0.0)).unwrap();
+        assert_near(n.normalize(), Vec3::new(0.5, 1.0, 0.0).normalize());
+        let tangent = m.transform_vector(Vec3::new(1.0, -1.0, 0.0));
+        assert!(n.dot(tangent).abs() < 1e-12);
+        // Naively transformed normals aren't perpendicular anymore.
+        assert!(
+            m.transform_vector(Vec3::new(1.0, 1.0, 0.0))
+                .dot(tangent)
+                .abs()
+                > 1.0
+        );
+
+        // Flattening matrices have no normal transform.
+        let flat = Mat4::from_mat3(Mat3::scale(Vec3::new(1.0, 1.0, 0.0)), Vec3::default());
+        assert!(flat.transform_normal(Vec3::z(1.0)).is_none());
+    }
+
+    #[test]
+    fn quat() {
+        let axis = Vec3::new(1.0, 2.0, -1.0);
+        let q = Quat::rotation(70.0, axis);
+        let v = Vec3::new(0.3, -1.0, 2.0);
+        assert!((q.length() - 1.0).abs() < 1e-12);
+        assert_near(q.rotate(v), Mat3::rotation(70.0, axis) * v);
+        assert_mat3_near(q.to_mat3(), Mat3::rotation(70.0, axis));
+        assert_near(q.conjugate().rotate(q.rotate(v)), v);
+
+        // Products rotate by the right quaternion first.
+        let a = Quat::rotation(90.0, Vec3::x(1.0));
+        let b = Quat::rotation(90.0, Vec3::z(1.0));
+        assert_near((a * b).rotate(v), a.rotate(b.rotate(v)));
+        assert_mat3_near((a * b).to_mat3(), a.to_mat3() * b.to_mat3());
+
+        // Slerp goes from one rotation to the other at constant speed.
+        let from = Quat::rotation(10.0, axis);
+        let to = Quat::rotation(130.0, axis);
+        assert_near(from.slerp(to, 0.0).rotate(v), from.rotate(v));
+        assert_near(from.slerp(to, 1.0).rotate(v), to.rotate(v));
+        let quarter = Quat::rotation(40.0, axis);
+        assert_near(from.slerp(to, 0.25).rotate(v), quarter.rotate(v));
+        assert!((from.slerp(to, 0.6).length() - 1.0).abs() < 1e-12);
+
+        // It takes the shorter way, also when the quaternions are in opposite hemispheres.
+        let to = Quat::rotation(350.0, Vec3::z(1.0));
+        let mid = Quat::IDENTITY.slerp(to, 0.5);
+        assert_near(
+            mid.rotate(Vec3::x(1.0)),
+            Mat3::rotation(-5.0, Vec3::z(1.0)) * Vec3::x(1.0),
+        );
+
+        // Nearly equal rotations don't divide by zero.
+        let near = Quat::rotation(10.0 + 1e-9, axis);
+        assert_near(from.slerp(near, 0.5).rotate(v), from.rotate(v));
+    }
+
+    #[test]
+    fn onb() {
+        let check = |b: Onb| {
+            for (x, y) in [(b.u, b.v), (b.v, b.w), (b.w, b.u)] {
+                assert!(x.dot(y).abs() < 1e-12);
+            }
+            for x in [b.u, b.v, b.w] {
+                assert!((x.length() - 1.0).abs() < 1e-12);
+            }
+            // from_w may be left-handed, which sampling doesn't mind.
+            assert_near(b.u.cross(b.v).cross(b.w), Vec3::default());
+
+            let a = Vec3::new(0.3, -1.0, 2.0);
+            assert_near(b.to_local(b.local(a)), a);
+            assert_near(b.to_mat3() * a, b.local(a));
+        };
+        for n in [Vec3::z(1.0), Vec3::z(-1.0), Vec3::new(1.0, 2.0, 3.0)] {
+            let b = Onb::from_w(n);
+            assert_near(b.w, n.normalize());
+            check(b);
+        }
+
+        let b = Onb::from_w_up(Vec3::new(0.0, 1.0, 1.0), Vec3::y(1.0));
+        check(b);
+        assert_near(b.u.cross(b.v), b.w);
+        assert_near(b.u, Vec3::x(1.0));
+        assert!(b.v.y > 0.0);
+    }
 }